@@ -0,0 +1,506 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+use crate::{
+    board::{
+        in_board, in_country, in_palace, sort_moves, Board, Chess, ChessType, Player, Position,
+        BOARD_HEIGHT, BOARD_WIDTH,
+    },
+    compact::CompactMove,
+    movegen::{GenType, MoveList},
+};
+
+const SQUARE_COUNT: usize = (BOARD_WIDTH * BOARD_HEIGHT) as usize;
+
+// 90个格子用u128的低90位表示，第row*9+col位对应棋盘上的(row, col)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct BitBoard(pub u128);
+
+impl BitBoard {
+    pub const EMPTY: BitBoard = BitBoard(0);
+
+    pub fn from_square(sq: usize) -> Self {
+        BitBoard(1 << sq)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+    pub fn has(&self, sq: usize) -> bool {
+        self.0 & (1 << sq) != 0
+    }
+    pub fn set(&mut self, sq: usize) {
+        self.0 |= 1 << sq;
+    }
+    pub fn clear(&mut self, sq: usize) {
+        self.0 &= !(1 << sq);
+    }
+    pub fn count(&self) -> u32 {
+        self.0.count_ones()
+    }
+    // 取出最低位的格子
+    pub fn lsb(&self) -> Option<usize> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(self.0.trailing_zeros() as usize)
+        }
+    }
+}
+
+impl Iterator for BitBoard {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        let sq = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(sq)
+    }
+}
+
+impl BitAnd for BitBoard {
+    type Output = BitBoard;
+    fn bitand(self, rhs: BitBoard) -> BitBoard {
+        BitBoard(self.0 & rhs.0)
+    }
+}
+impl BitAndAssign for BitBoard {
+    fn bitand_assign(&mut self, rhs: BitBoard) {
+        self.0 &= rhs.0;
+    }
+}
+impl BitOr for BitBoard {
+    type Output = BitBoard;
+    fn bitor(self, rhs: BitBoard) -> BitBoard {
+        BitBoard(self.0 | rhs.0)
+    }
+}
+impl BitOrAssign for BitBoard {
+    fn bitor_assign(&mut self, rhs: BitBoard) {
+        self.0 |= rhs.0;
+    }
+}
+impl Not for BitBoard {
+    type Output = BitBoard;
+    fn not(self) -> BitBoard {
+        BitBoard(!self.0 & ((1 << SQUARE_COUNT) - 1))
+    }
+}
+
+pub fn square(pos: Position) -> usize {
+    (pos.row * BOARD_WIDTH + pos.col) as usize
+}
+
+pub fn position(sq: usize) -> Position {
+    Position::new(sq as i32 / BOARD_WIDTH, sq as i32 % BOARD_WIDTH)
+}
+
+// 预先计算好的走法表
+pub struct AttackTables {
+    // 马腿和对应的落点，[格子][方向] => (马腿, 落点)
+    knight: [[Option<(usize, BitBoard)>; 4]; SQUARE_COUNT],
+    // 从将帅出发反查马的位置，马腿在将帅的斜角上
+    knight_check: [[Option<(usize, BitBoard)>; 4]; SQUARE_COUNT],
+    // 象眼和对应的落点
+    bishop: [[Option<(usize, usize)>; 4]; SQUARE_COUNT],
+    // [玩家][格子]
+    king: [[BitBoard; SQUARE_COUNT]; 2],
+    advisor: [[BitBoard; SQUARE_COUNT]; 2],
+    pawn: [[BitBoard; SQUARE_COUNT]; 2],
+    // 能吃到某个格子的兵的位置，[兵的玩家][格子]
+    pawn_check: [[BitBoard; SQUARE_COUNT]; 2],
+    // 己方半边棋盘
+    country: [BitBoard; 2],
+    // 横向的车和炮，[列][该行的占位] => 该行的落点
+    rank_rook: Vec<[u16; 1 << BOARD_WIDTH]>,
+    rank_cannon: Vec<[u16; 1 << BOARD_WIDTH]>,
+    // 纵向的车和炮，[行][该列的占位] => 该列的落点
+    file_rook: Vec<[u16; 1 << BOARD_HEIGHT]>,
+    file_cannon: Vec<[u16; 1 << BOARD_HEIGHT]>,
+    // 把一列的占位展开成位棋盘，[列][该列的占位]
+    file_spread: Vec<[BitBoard; 1 << BOARD_HEIGHT]>,
+}
+
+// 在一条线上计算车的落点（碰到的第一个棋子也算在内）和炮的吃子落点
+fn slide_line(index: usize, occupied: u16, len: usize) -> (u16, u16) {
+    let mut rook = 0;
+    let mut cannon = 0;
+    for step in [-1i32, 1] {
+        let mut has_chess = false;
+        let mut i = index as i32 + step;
+        while i >= 0 && i < len as i32 {
+            let bit = 1 << i;
+            if !has_chess {
+                rook |= bit;
+                if occupied & bit != 0 {
+                    has_chess = true;
+                }
+            } else if occupied & bit != 0 {
+                cannon |= bit;
+                break;
+            }
+            i += step;
+        }
+    }
+    (rook, cannon)
+}
+
+fn to_bitboard(targets: &[Position], valid: impl Fn(Position) -> bool) -> BitBoard {
+    let mut bb = BitBoard::EMPTY;
+    for target in targets {
+        if in_board(*target) && valid(*target) {
+            bb.set(square(*target));
+        }
+    }
+    bb
+}
+
+impl AttackTables {
+    fn new() -> Box<Self> {
+        let mut tables = Box::new(AttackTables {
+            knight: [[None; 4]; SQUARE_COUNT],
+            knight_check: [[None; 4]; SQUARE_COUNT],
+            bishop: [[None; 4]; SQUARE_COUNT],
+            king: [[BitBoard::EMPTY; SQUARE_COUNT]; 2],
+            advisor: [[BitBoard::EMPTY; SQUARE_COUNT]; 2],
+            pawn: [[BitBoard::EMPTY; SQUARE_COUNT]; 2],
+            pawn_check: [[BitBoard::EMPTY; SQUARE_COUNT]; 2],
+            country: [BitBoard::EMPTY; 2],
+            rank_rook: vec![[0; 1 << BOARD_WIDTH]; BOARD_WIDTH as usize],
+            rank_cannon: vec![[0; 1 << BOARD_WIDTH]; BOARD_WIDTH as usize],
+            file_rook: vec![[0; 1 << BOARD_HEIGHT]; BOARD_HEIGHT as usize],
+            file_cannon: vec![[0; 1 << BOARD_HEIGHT]; BOARD_HEIGHT as usize],
+            file_spread: vec![[BitBoard::EMPTY; 1 << BOARD_HEIGHT]; BOARD_WIDTH as usize],
+        });
+        for sq in 0..SQUARE_COUNT {
+            let pos = position(sq);
+            let legs = [pos.up(1), pos.down(1), pos.left(1), pos.right(1)];
+            let knight_targets = [
+                [pos.up(2).left(1), pos.up(2).right(1)],
+                [pos.down(2).left(1), pos.down(2).right(1)],
+                [pos.up(1).left(2), pos.down(1).left(2)],
+                [pos.up(1).right(2), pos.down(1).right(2)],
+            ];
+            let eyes = [
+                pos.up(1).left(1),
+                pos.up(1).right(1),
+                pos.down(1).left(1),
+                pos.down(1).right(1),
+            ];
+            let knight_checks = [
+                [pos.up(2).left(1), pos.up(1).left(2)],
+                [pos.up(2).right(1), pos.up(1).right(2)],
+                [pos.down(2).left(1), pos.down(1).left(2)],
+                [pos.down(2).right(1), pos.down(1).right(2)],
+            ];
+            let bishop_targets = [
+                pos.up(2).left(2),
+                pos.up(2).right(2),
+                pos.down(2).left(2),
+                pos.down(2).right(2),
+            ];
+            for d in 0..4 {
+                if in_board(legs[d]) {
+                    let targets = to_bitboard(&knight_targets[d], |_| true);
+                    tables.knight[sq][d] = Some((square(legs[d]), targets));
+                }
+                if in_board(eyes[d]) {
+                    let targets = to_bitboard(&knight_checks[d], |_| true);
+                    tables.knight_check[sq][d] = Some((square(eyes[d]), targets));
+                    if in_board(bishop_targets[d]) {
+                        tables.bishop[sq][d] = Some((square(eyes[d]), square(bishop_targets[d])));
+                    }
+                }
+            }
+            for player in [Player::Red, Player::Black] {
+                let p = player.value() as usize;
+                tables.king[p][sq] = to_bitboard(&legs, |t| in_palace(t, player));
+                tables.advisor[p][sq] = to_bitboard(&eyes, |t| in_palace(t, player));
+                let forward = if player == Player::Black {
+                    pos.down(1)
+                } else {
+                    pos.up(1)
+                };
+                tables.pawn[p][sq] = if in_country(pos.row, player) {
+                    to_bitboard(&[forward], |_| true)
+                } else {
+                    to_bitboard(&[forward, pos.left(1), pos.right(1)], |_| true)
+                };
+                if in_country(pos.row, player) {
+                    tables.country[p].set(sq);
+                }
+            }
+        }
+        for p in 0..2 {
+            for sq in 0..SQUARE_COUNT {
+                for target in tables.pawn[p][sq] {
+                    tables.pawn_check[p][target].set(sq);
+                }
+            }
+        }
+        for col in 0..BOARD_WIDTH as usize {
+            for occupied in 0..1 << BOARD_WIDTH {
+                let (rook, cannon) = slide_line(col, occupied, BOARD_WIDTH as usize);
+                tables.rank_rook[col][occupied as usize] = rook;
+                tables.rank_cannon[col][occupied as usize] = cannon;
+            }
+            for rows in 0..1usize << BOARD_HEIGHT {
+                let mut bb = BitBoard::EMPTY;
+                for row in 0..BOARD_HEIGHT as usize {
+                    if rows & (1 << row) != 0 {
+                        bb.set(row * BOARD_WIDTH as usize + col);
+                    }
+                }
+                tables.file_spread[col][rows] = bb;
+            }
+        }
+        for row in 0..BOARD_HEIGHT as usize {
+            for occupied in 0..1 << BOARD_HEIGHT {
+                let (rook, cannon) = slide_line(row, occupied, BOARD_HEIGHT as usize);
+                tables.file_rook[row][occupied as usize] = rook;
+                tables.file_cannon[row][occupied as usize] = cannon;
+            }
+        }
+        tables
+    }
+}
+
+lazy_static! {
+    pub static ref ATTACK_TABLES: Box<AttackTables> = AttackTables::new();
+}
+
+// 位棋盘表示的局面，和Board::chesses保持同步
+#[derive(Clone, Debug)]
+pub struct BitBoards {
+    // [玩家][棋子类型]
+    pub pieces: [[BitBoard; 7]; 2],
+    pub occupied: [BitBoard; 2],
+    // 每一行、每一列的占位，用于车和炮查表
+    ranks: [u16; BOARD_HEIGHT as usize],
+    files: [u16; BOARD_WIDTH as usize],
+}
+
+impl BitBoards {
+    pub fn new() -> Self {
+        BitBoards {
+            pieces: [[BitBoard::EMPTY; 7]; 2],
+            occupied: [BitBoard::EMPTY; 2],
+            ranks: [0; BOARD_HEIGHT as usize],
+            files: [0; BOARD_WIDTH as usize],
+        }
+    }
+    pub fn all(&self) -> BitBoard {
+        self.occupied[0] | self.occupied[1]
+    }
+    pub fn pieces_of(&self, player: Player, ct: ChessType) -> BitBoard {
+        self.pieces[player.value() as usize][ct.value() as usize]
+    }
+    // 把pos上的棋子从old换成new
+    pub fn replace(&mut self, pos: Position, old: Chess, new: Chess) {
+        let sq = square(pos);
+        if let (Some(player), Some(ct)) = (old.player(), old.chess_type()) {
+            self.pieces[player.value() as usize][ct.value() as usize].clear(sq);
+            self.occupied[player.value() as usize].clear(sq);
+            self.ranks[pos.row as usize] &= !(1 << pos.col);
+            self.files[pos.col as usize] &= !(1 << pos.row);
+        }
+        if let (Some(player), Some(ct)) = (new.player(), new.chess_type()) {
+            self.pieces[player.value() as usize][ct.value() as usize].set(sq);
+            self.occupied[player.value() as usize].set(sq);
+            self.ranks[pos.row as usize] |= 1 << pos.col;
+            self.files[pos.col as usize] |= 1 << pos.row;
+        }
+    }
+    // 车的落点，包括碰到的第一个棋子
+    pub fn rook_attacks(&self, sq: usize) -> BitBoard {
+        let tables = &*ATTACK_TABLES;
+        let (row, col) = (sq / BOARD_WIDTH as usize, sq % BOARD_WIDTH as usize);
+        let rank = tables.rank_rook[col][self.ranks[row] as usize];
+        let file = tables.file_rook[row][self.files[col] as usize];
+        BitBoard((rank as u128) << (row * BOARD_WIDTH as usize))
+            | tables.file_spread[col][file as usize]
+    }
+    // 炮隔子能吃到的格子
+    pub fn cannon_attacks(&self, sq: usize) -> BitBoard {
+        let tables = &*ATTACK_TABLES;
+        let (row, col) = (sq / BOARD_WIDTH as usize, sq % BOARD_WIDTH as usize);
+        let rank = tables.rank_cannon[col][self.ranks[row] as usize];
+        let file = tables.file_cannon[row][self.files[col] as usize];
+        BitBoard((rank as u128) << (row * BOARD_WIDTH as usize))
+            | tables.file_spread[col][file as usize]
+    }
+    pub fn knight_attacks(&self, sq: usize) -> BitBoard {
+        let all = self.all();
+        let mut targets = BitBoard::EMPTY;
+        for (leg, bb) in ATTACK_TABLES.knight[sq].iter().flatten() {
+            if !all.has(*leg) {
+                targets |= *bb;
+            }
+        }
+        targets
+    }
+    // 某一方的棋子从sq出发的所有落点（不区分吃子和己方棋子）
+    pub fn targets(&self, ct: ChessType, sq: usize, player: Player) -> BitBoard {
+        let tables = &*ATTACK_TABLES;
+        let p = player.value() as usize;
+        match ct {
+            ChessType::King => tables.king[p][sq],
+            ChessType::Advisor => tables.advisor[p][sq],
+            ChessType::Bishop => {
+                let all = self.all();
+                let mut targets = BitBoard::EMPTY;
+                for (eye, target) in tables.bishop[sq].iter().flatten() {
+                    if !all.has(*eye) {
+                        targets.set(*target);
+                    }
+                }
+                targets & tables.country[p]
+            }
+            ChessType::Knight => self.knight_attacks(sq),
+            ChessType::Rook => self.rook_attacks(sq),
+            ChessType::Cannon => (self.rook_attacks(sq) & !self.all()) | self.cannon_attacks(sq),
+            ChessType::Pawn => tables.pawn[p][sq],
+        }
+    }
+    pub fn is_checked(&self, player: Player) -> bool {
+        let tables = &*ATTACK_TABLES;
+        let enemy = player.next();
+        let king = match self.pieces_of(player, ChessType::King).lsb() {
+            Some(sq) => sq,
+            None => return false,
+        };
+        let rook_lines = self.rook_attacks(king);
+        // 是否被车将军，或者双方将帅照面
+        if !(rook_lines
+            & (self.pieces_of(enemy, ChessType::Rook) | self.pieces_of(enemy, ChessType::King)))
+        .is_empty()
+        {
+            return true;
+        }
+        // 是否被炮将军
+        if !(self.cannon_attacks(king) & self.pieces_of(enemy, ChessType::Cannon)).is_empty() {
+            return true;
+        }
+        // 是否被马将军，马腿在将帅的斜角上
+        let all = self.all();
+        let knights = self.pieces_of(enemy, ChessType::Knight);
+        for (leg, bb) in tables.knight_check[king].iter().flatten() {
+            if !all.has(*leg) && !(*bb & knights).is_empty() {
+                return true;
+            }
+        }
+        // 是否被兵将军
+        !(tables.pawn_check[enemy.value() as usize][king] & self.pieces_of(enemy, ChessType::Pawn))
+            .is_empty()
+    }
+}
+
+impl Default for BitBoards {
+    fn default() -> Self {
+        BitBoards::new()
+    }
+}
+
+impl Board {
+    pub fn generate_move_bitboard(&mut self, gen: GenType) -> MoveList {
+        self.gen_counter += 1;
+        let mut moves = MoveList::new();
+        let own = self.bitboards.occupied[self.turn.value() as usize];
+        let enemy = self.bitboards.occupied[self.turn.next().value() as usize];
+        let mask = match gen {
+            GenType::All => !own,
+            GenType::Captures => enemy,
+            GenType::Quiets => !(own | enemy),
+        };
+        for ct in [
+            ChessType::King,
+            ChessType::Advisor,
+            ChessType::Bishop,
+            ChessType::Knight,
+            ChessType::Rook,
+            ChessType::Cannon,
+            ChessType::Pawn,
+        ] {
+            for from in self.bitboards.pieces_of(self.turn, ct) {
+                let from_pos = position(from);
+                for to in self.bitboards.targets(ct, from, self.turn) & mask {
+                    moves.push(CompactMove::new(from_pos, position(to)));
+                }
+            }
+        }
+        sort_moves(self, &mut moves);
+        moves
+    }
+    pub fn is_checked_bitboard(&self, player: Player) -> bool {
+        self.bitboards.is_checked(player)
+    }
+}
+
+#[cfg(test)]
+fn perft_with(
+    board: &mut Board,
+    depth: i32,
+    generate: fn(&mut Board, GenType) -> MoveList,
+    is_checked: fn(&Board, Player) -> bool,
+) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut nodes = 0;
+    for m in generate(board, GenType::All) {
+        let m = m.to_move(board);
+        board.apply_move(&m);
+        if !is_checked(board, m.player) {
+            nodes += perft_with(board, depth - 1, generate, is_checked);
+        }
+        board.unapply_move(&m);
+    }
+    nodes
+}
+
+#[test]
+fn test_bitboard_sync() {
+    let mut board = Board::init();
+    for m in board.generate_move(false) {
+        let m = m.to_move(&board);
+        board.apply_move(&m);
+        for i in 0..BOARD_HEIGHT {
+            for j in 0..BOARD_WIDTH {
+                let pos = Position::new(i, j);
+                let chess = board.chess_at(pos);
+                let sq = square(pos);
+                assert_eq!(board.bitboards.all().has(sq), chess != Chess::None);
+                if let (Some(player), Some(ct)) = (chess.player(), chess.chess_type()) {
+                    assert!(board.bitboards.pieces_of(player, ct).has(sq));
+                }
+            }
+        }
+        board.unapply_move(&m);
+    }
+}
+
+#[test]
+fn test_bitboard_perft() {
+    for fen in [
+        "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+        "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+        "1cbak4/9/n2a5/2p1p3p/5cp2/2n2N3/6PCP/3AB4/2C6/3A1K1N1 w - - 0 1",
+        "5a3/3k5/3aR4/9/5r3/5n3/9/3A1A1N1/5K3/9 w - - 0 1",
+        "CRN1k1b2/3ca4/4ba3/9/2nr5/9/9/4B4/4A4/4KA3 w - - 0 1",
+        "R1N1k1b2/9/3aba3/9/2nr5/2B6/9/4B4/4A4/4KA3 w - - 0 1",
+        "3k5/9/9/9/9/4c4/9/4C4/9/4K4 w - - 0 1",
+        "3k5/4a4/9/9/9/9/9/9/4A4/4K4 w - - 0 1",
+    ] {
+        let mut board = Board::from_fen(fen).unwrap();
+        let mailbox = perft_with(
+            &mut board,
+            3,
+            Board::generate_move_mailbox,
+            Board::is_checked_mailbox,
+        );
+        let bitboard = perft_with(
+            &mut board,
+            3,
+            Board::generate_move_bitboard,
+            Board::is_checked_bitboard,
+        );
+        assert_eq!(mailbox, bitboard, "{}", fen);
+    }
+}
@@ -1,10 +1,15 @@
-use std::{collections::HashMap, vec};
+use std::{fmt, str::FromStr};
 
 use crate::{
+    bitboard::{square, BitBoards},
+    compact::CompactMove,
     constant::{
-        FEN_MAP, KILL, MAX, MAX_DEPTH, MIN, RECORD_SIZE, ZOBRIST_TABLE, ZOBRIST_TABLE_LOCK,
+        FEN_MAP, HASH_ZOBRIST, KILL, MAX, MAX_DEPTH, MIN, RECORD_SIZE, ZOBRIST_TABLE,
+        ZOBRIST_TABLE_LOCK,
     },
-    zobrist::Zobristable,
+    error::{FenError, MoveParseError},
+    movegen::{GenType, MoveList, STEP_TABLES},
+    picker::MovePicker,
 };
 
 pub const BOARD_WIDTH: i32 = 9;
@@ -48,6 +53,28 @@ impl Chess {
             Chess::None => None,
         }
     }
+    pub fn of(player: Player, ct: ChessType) -> Chess {
+        match player {
+            Player::Black => Chess::Black(ct),
+            Player::Red => Chess::Red(ct),
+        }
+    }
+    // FEN里的字母，红方大写，黑方小写
+    pub fn fen_char(&self) -> Option<char> {
+        let c = match self.chess_type()? {
+            ChessType::King => 'k',
+            ChessType::Advisor => 'a',
+            ChessType::Bishop => 'b',
+            ChessType::Knight => 'n',
+            ChessType::Rook => 'r',
+            ChessType::Cannon => 'c',
+            ChessType::Pawn => 'p',
+        };
+        match self {
+            Chess::Red(_) => Some(c.to_ascii_uppercase()),
+            _ => Some(c),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -147,7 +174,7 @@ impl Position {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Move {
     pub player: Player, // 玩家
     pub from: Position, // 起手位置
@@ -179,21 +206,28 @@ impl Move {
     }
 }
 
-impl From<&str> for Position {
-    fn from(m: &str) -> Self {
-        let mb = m.as_bytes();
-        Position::new(
-            BOARD_HEIGHT - 1 - (mb[1] - '0' as u8) as i32,
-            (mb[0] - 'a' as u8) as i32,
-        )
+impl FromStr for Position {
+    type Err = MoveParseError;
+
+    // ICCS坐标，例如h2
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            [file @ b'a'..=b'i', rank @ b'0'..=b'9'] => Ok(Position::new(
+                BOARD_HEIGHT - 1 - (rank - b'0') as i32,
+                (file - b'a') as i32,
+            )),
+            [_, _] => Err(MoveParseError::InvalidSquare(s.to_owned())),
+            _ => Err(MoveParseError::InvalidLength(s.to_owned())),
+        }
     }
 }
-impl ToString for Position {
-    fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
             "{}{}",
-            char::from_u32((self.col as u8 + 'a' as u8) as u32).unwrap(),
-            char::from_u32(((BOARD_HEIGHT as u8 - 1 - self.row as u8) + '0' as u8) as u32).unwrap()
+            (self.col as u8 + b'a') as char,
+            (BOARD_HEIGHT as u8 - 1 - self.row as u8 + b'0') as char
         )
     }
 }
@@ -202,9 +236,8 @@ impl ToString for Position {
 pub struct Record {
     pub value: i32,
     pub depth: i32,
-    pub best_move: Option<Move>,
+    pub best_move: Option<CompactMove>,
     pub zobrist_lock: u64,
-    pub turn: Player,
 }
 
 pub struct Board {
@@ -214,11 +247,27 @@ pub struct Board {
     pub counter: i32,
     pub gen_counter: i32,
     pub move_history: Vec<Move>,
-    pub best_moves_last: Vec<Move>,
+    pub best_moves_last: Vec<CompactMove>,
     pub records: Vec<Option<Record>>,
     pub zobrist_value: u64,
     pub zobrist_value_lock: u64,
     pub distance: i32,
+    // 双方的子力与位置分之和，随着走子增量更新
+    pub values: [i32; 2],
+    // 与chesses同步的位棋盘表示
+    pub bitboards: BitBoards,
+    // 每一层的两个杀手着法
+    pub killers: Vec<[Option<CompactMove>; 2]>,
+    // 历史表，[起点][终点]
+    pub history: Vec<i32>,
+    // 距离上次吃子的半回合数
+    pub halfmove_clock: i32,
+    // 回合数，黑方走完后加一
+    pub fullmove_number: i32,
+    // 吃子前的halfmove_clock，撤销吃子时恢复
+    clock_history: Vec<i32>,
+    // 每一步之前局面的zobrist值和这一步，判断重复局面用
+    pub position_history: Vec<(u64, Move)>,
 }
 
 // 棋子是否在棋盘内
@@ -338,6 +387,38 @@ const PAWN_VALUE_TABLE: [[i32; BOARD_WIDTH as usize]; BOARD_HEIGHT as usize] = [
 
 const INITIATIVE_BONUS: i32 = 3;
 
+// 历史表的下标，[起点][终点]
+fn history_index(from: Position, to: Position) -> usize {
+    square(from) * (BOARD_WIDTH * BOARD_HEIGHT) as usize + square(to)
+}
+
+// 按吃子价值排序，吃的子越大、用的子越小越靠前
+pub fn sort_moves(board: &Board, moves: &mut [CompactMove]) {
+    moves.sort_unstable_by_key(|m| {
+        board.chess_at(m.source()).value() - board.chess_at(m.target()).value()
+    });
+}
+
+// 棋子在某个位置上的分值（子力+位置），黑方的棋子按照翻转后的位置查表
+pub fn position_value(chess: Chess, pos: Position) -> i32 {
+    let pos = if chess.belong_to(Player::Black) {
+        pos.flip()
+    } else {
+        pos
+    };
+    let (row, col) = (pos.row as usize, pos.col as usize);
+    match chess.chess_type() {
+        Some(ChessType::King) => KING_VALUE_TABLE[row][col],
+        Some(ChessType::Advisor) => ADVISOR_VALUE_TABLE[row][col],
+        Some(ChessType::Bishop) => BISHOP_VALUE_TABLE[row][col],
+        Some(ChessType::Knight) => KNIGHT_VALUE_TABLE[row][col],
+        Some(ChessType::Rook) => ROOK_VALUE_TABLE[row][col],
+        Some(ChessType::Cannon) => CANNON_VALUE_TABLE[row][col],
+        Some(ChessType::Pawn) => PAWN_VALUE_TABLE[row][col],
+        None => 0,
+    }
+}
+
 const RECORD_NONE: Option<Record> = None;
 impl Board {
     pub fn init() -> Self {
@@ -463,9 +544,26 @@ impl Board {
             zobrist_value: 0,
             zobrist_value_lock: 0,
             distance: 0,
+            values: [0, 0],
+            bitboards: BitBoards::new(),
+            killers: vec![[None; 2]; MAX_DEPTH as usize + 1],
+            history: vec![0; (BOARD_WIDTH * BOARD_HEIGHT * BOARD_WIDTH * BOARD_HEIGHT) as usize],
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            clock_history: vec![],
+            position_history: vec![],
         };
-        board.zobrist_value = ZOBRIST_TABLE.calc_chesses(&board.chesses);
-        board.zobrist_value_lock = ZOBRIST_TABLE_LOCK.calc_chesses(&board.chesses);
+        for i in 0..BOARD_HEIGHT {
+            for j in 0..BOARD_WIDTH {
+                let pos = Position::new(i, j);
+                board
+                    .bitboards
+                    .replace(pos, Chess::None, board.chess_at(pos));
+            }
+        }
+        board.zobrist_value = ZOBRIST_TABLE.calc_board(&board.chesses, board.turn);
+        board.zobrist_value_lock = ZOBRIST_TABLE_LOCK.calc_board(&board.chesses, board.turn);
+        board.values = board.calc_values();
         board
     }
     pub fn empty() -> Self {
@@ -480,58 +578,150 @@ impl Board {
             zobrist_value: 0,
             zobrist_value_lock: 0,
             distance: 0,
+            values: [0, 0],
+            bitboards: BitBoards::new(),
+            killers: vec![[None; 2]; MAX_DEPTH as usize + 1],
+            history: vec![0; (BOARD_WIDTH * BOARD_HEIGHT * BOARD_WIDTH * BOARD_HEIGHT) as usize],
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            clock_history: vec![],
+            position_history: vec![],
         }
     }
-    pub fn from_fen(fen: &str) -> Self {
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
         let mut board = Board::empty();
-        let mut parts = fen.split(" ");
-        let pos = parts.next().unwrap();
-        let mut i = 0;
-        for row in pos.split("/") {
+        let mut parts = fen.split_whitespace();
+        let pos = parts.next().ok_or(FenError::Empty)?;
+        let rows = pos.split('/').collect::<Vec<&str>>();
+        if rows.len() != BOARD_HEIGHT as usize {
+            return Err(FenError::RowCount(rows.len()));
+        }
+        for (i, row) in rows.into_iter().enumerate() {
             let mut j = 0;
             for col in row.chars() {
-                if col.is_numeric() {
-                    j += col.to_digit(10).unwrap() as i32;
+                if let Some(n) = col.to_digit(10) {
+                    j += n as i32;
                 } else {
-                    if let Some(chess) = (*FEN_MAP).get(&col) {
-                        board.set_chess(Position::new(i, j), chess.to_owned());
+                    let chess = (*FEN_MAP).get(&col).ok_or(FenError::InvalidPiece(col))?;
+                    if j >= BOARD_WIDTH {
+                        return Err(FenError::RowLength(i));
                     }
+                    board.set_chess(Position::new(i as i32, j), chess.to_owned());
                     j += 1;
                 }
             }
-            i += 1;
+            if j != BOARD_WIDTH {
+                return Err(FenError::RowLength(i));
+            }
         }
-        board.zobrist_value = ZOBRIST_TABLE.calc_chesses(&board.chesses);
-        board.zobrist_value_lock = ZOBRIST_TABLE_LOCK.calc_chesses(&board.chesses);
-        let turn = parts.next().unwrap();
-        if turn == "b" {
-            board.turn = Player::Black;
+        board.turn = match parts.next() {
+            Some("w") | Some("r") => Player::Red,
+            Some("b") => Player::Black,
+            Some(side) => return Err(FenError::InvalidSide(side.to_owned())),
+            None => return Err(FenError::MissingSide),
+        };
+        // 跳过国际象棋里的易位和吃过路兵两项
+        let mut parts = parts.skip(2);
+        if let Some(halfmove) = parts.next() {
+            board.halfmove_clock = halfmove
+                .parse()
+                .map_err(|_| FenError::InvalidCounter(halfmove.to_owned()))?;
         }
-        board
+        if let Some(fullmove) = parts.next() {
+            board.fullmove_number = fullmove
+                .parse()
+                .map_err(|_| FenError::InvalidCounter(fullmove.to_owned()))?;
+        }
+        board.zobrist_value = ZOBRIST_TABLE.calc_board(&board.chesses, board.turn);
+        board.zobrist_value_lock = ZOBRIST_TABLE_LOCK.calc_board(&board.chesses, board.turn);
+        board.values = board.calc_values();
+        Ok(board)
+    }
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for (i, row) in self.chesses.iter().enumerate() {
+            if i > 0 {
+                fen.push('/');
+            }
+            let mut empty = 0;
+            for chess in row {
+                if let Some(c) = chess.fen_char() {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen.push(c);
+                } else {
+                    empty += 1;
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+        }
+        let turn = if self.turn == Player::Red { 'w' } else { 'b' };
+        format!(
+            "{} {} - - {} {}",
+            fen, turn, self.halfmove_clock, self.fullmove_number
+        )
     }
     pub fn apply_move(&mut self, m: &Move) {
+        self.position_history.push((self.zobrist_value, *m));
         let chess = self.chess_at(m.from);
         self.set_chess(m.to, chess);
         self.set_chess(m.from, Chess::None);
         self.zobrist_value = ZOBRIST_TABLE.apply_move(self.zobrist_value, m);
         self.zobrist_value_lock = ZOBRIST_TABLE_LOCK.apply_move(self.zobrist_value_lock, m);
+        let player = m.chess.player().unwrap().value() as usize;
+        self.values[player] += position_value(m.chess, m.to) - position_value(m.chess, m.from);
+        if let Some(captured) = m.capture.player() {
+            self.values[captured.value() as usize] -= position_value(m.capture, m.to);
+        }
+        if m.capture == Chess::None {
+            self.halfmove_clock += 1;
+        } else {
+            self.clock_history.push(self.halfmove_clock);
+            self.halfmove_clock = 0;
+        }
+        if m.player == Player::Black {
+            self.fullmove_number += 1;
+        }
         self.turn = m.player.next();
     }
     pub fn do_move(&mut self, m: &Move) {
         self.apply_move(m);
         self.distance += 1;
-        self.move_history.push(m.clone());
+        self.move_history.push(*m);
     }
+    // do_move的逆操作，只和do_move配对；不记入搜索路径的试走用apply_move和unapply_move
     pub fn undo_move(&mut self, m: &Move) {
+        self.unapply_move(m);
+        self.distance -= 1;
+        self.move_history.pop();
+    }
+    // apply_move的逆操作，不改动搜索用的distance和move_history
+    pub fn unapply_move(&mut self, m: &Move) {
+        self.position_history.pop();
         let chess = self.chess_at(m.to);
         self.set_chess(m.from, chess);
         self.set_chess(m.to, m.capture);
         self.zobrist_value = ZOBRIST_TABLE.undo_move(self.zobrist_value, m);
         self.zobrist_value_lock = ZOBRIST_TABLE_LOCK
             .undo_move(self.zobrist_value_lock, m);
+        let player = m.chess.player().unwrap().value() as usize;
+        self.values[player] -= position_value(m.chess, m.to) - position_value(m.chess, m.from);
+        if let Some(captured) = m.capture.player() {
+            self.values[captured.value() as usize] += position_value(m.capture, m.to);
+        }
+        if m.capture == Chess::None {
+            self.halfmove_clock -= 1;
+        } else {
+            self.halfmove_clock = self.clock_history.pop().unwrap_or(0);
+        }
+        if m.player == Player::Black {
+            self.fullmove_number -= 1;
+        }
         self.turn = m.player;
-        self.distance -= 1;
-        self.move_history.pop();
     }
     pub fn chess_at(&self, pos: Position) -> Chess {
         if in_board(pos) {
@@ -541,6 +731,7 @@ impl Board {
         }
     }
     pub fn set_chess(&mut self, pos: Position, chess: Chess) {
+        self.bitboards.replace(pos, self.chess_at(pos), chess);
         self.chesses[pos.row as usize][pos.col as usize] = chess;
     }
     pub fn has_chess_between(&self, posa: Position, posb: Position) -> bool {
@@ -565,7 +756,7 @@ impl Board {
                 }
             }
         }
-        return false;
+        false
     }
     pub fn king_position(&self, player: Player) -> Option<Position> {
         if player == Player::Black {
@@ -597,341 +788,192 @@ impl Board {
         }
     }
     pub fn is_checked(&self, player: Player) -> bool {
+        if cfg!(feature = "bitboard") {
+            self.is_checked_bitboard(player)
+        } else {
+            self.is_checked_mailbox(player)
+        }
+    }
+    pub fn is_checked_mailbox(&self, player: Player) -> bool {
         let position_base = self.king_position(player).unwrap();
+        let sq = square(position_base);
+        let enemy = player.next();
 
-        // 是否被炮将军
-        let targets = self.generate_move_for_chess_type(ChessType::Cannon, position_base);
-        for pos in targets {
-            if self.chess_at(pos).belong_to(player.next()) {
-                if let Some(ChessType::Cannon) = self.chess_at(pos).chess_type() {
-                    return true;
-                }
-            }
-        }
-        // 是否被车将军
-        let targets = self.generate_move_for_chess_type(ChessType::Rook, position_base);
-        for pos in targets {
-            if self.chess_at(pos).belong_to(player.next()) {
-                if let Some(ChessType::Rook) = self.chess_at(pos).chess_type() {
-                    return true;
+        // 是否被车、炮将军，或者双方将帅照面
+        for (dr, dc) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let mut pos = Position::new(position_base.row + dr, position_base.col + dc);
+            let mut has_chess = false;
+            while in_board(pos) {
+                let chess = self.chess_at(pos);
+                if chess != Chess::None {
+                    if !has_chess {
+                        if chess == Chess::of(enemy, ChessType::Rook)
+                            || chess == Chess::of(enemy, ChessType::King)
+                        {
+                            return true;
+                        }
+                        has_chess = true;
+                    } else {
+                        if chess == Chess::of(enemy, ChessType::Cannon) {
+                            return true;
+                        }
+                        break;
+                    }
                 }
+                pos = Position::new(pos.row + dr, pos.col + dc);
             }
         }
 
         // 是否被马将军
-        let mut targets = vec![];
-        if self.chess_at(position_base.up(1).left(1)) == Chess::None {
-            targets.push(position_base.up(2).left(1));
-            targets.push(position_base.up(1).left(2));
-        }
-        if self.chess_at(position_base.down(1).left(1)) == Chess::None {
-            targets.push(position_base.down(2).left(1));
-            targets.push(position_base.down(1).left(2));
-        }
-        if self.chess_at(position_base.up(1).right(1)) == Chess::None {
-            targets.push(position_base.up(2).right(1));
-            targets.push(position_base.up(1).right(2));
-        }
-        if self.chess_at(position_base.down(1).right(1)) == Chess::None {
-            targets.push(position_base.down(2).right(1));
-            targets.push(position_base.down(1).right(2));
-        }
-        for pos in targets {
-            if self.chess_at(pos).belong_to(player.next()) {
-                if let Some(ChessType::Knight) = self.chess_at(pos).chess_type() {
-                    return true;
-                }
+        let tables = &*STEP_TABLES;
+        for step in tables.knight_check[sq].iter() {
+            if self.chess_at(step.to) == Chess::of(enemy, ChessType::Knight)
+                && self.chess_at(step.block.unwrap()) == Chess::None
+            {
+                return true;
             }
         }
 
         // 是否被兵将军
-        for pos in vec![
-            position_base.left(1),
-            position_base.right(1),
-            if player == Player::Red {
-                position_base.up(1)
-            } else {
-                position_base.down(1)
-            },
-        ] {
-            if self.chess_at(pos).belong_to(player.next()) {
-                if let Some(ChessType::Pawn) = self.chess_at(pos).chess_type() {
-                    return true;
-                }
+        for step in tables.pawn_check[enemy.value() as usize][sq].iter() {
+            if self.chess_at(step.to) == Chess::of(enemy, ChessType::Pawn) {
+                return true;
             }
         }
-        return self.king_eye_to_eye();
+        false
     }
-    pub fn generate_move_for_chess_type(
+    // 把一个棋子的所有落点写入着法列表
+    pub fn generate_move_for_chess(
         &self,
-        ct: ChessType,
+        chess: Chess,
         position_base: Position,
-    ) -> Vec<Position> {
-        let mut targets = vec![];
-        match ct {
-            ChessType::King => {
-                targets.append(&mut vec![
-                    position_base.up(1),
-                    position_base.down(1),
-                    position_base.left(1),
-                    position_base.right(1),
-                ]);
-            }
-            ChessType::Advisor => {
-                targets.append(&mut vec![
-                    position_base.up(1).left(1),
-                    position_base.up(1).right(1),
-                    position_base.down(1).left(1),
-                    position_base.down(1).right(1),
-                ]);
-            }
-            ChessType::Bishop => {
-                if self.chess_at(position_base.up(1).left(1)) == Chess::None {
-                    targets.push(position_base.up(2).left(2));
-                }
-                if self.chess_at(position_base.up(1).right(1)) == Chess::None {
-                    targets.push(position_base.up(2).right(2));
-                }
-                if self.chess_at(position_base.down(1).left(1)) == Chess::None {
-                    targets.push(position_base.down(2).left(2));
-                }
-                if self.chess_at(position_base.down(1).right(1)) == Chess::None {
-                    targets.push(position_base.down(2).right(2));
-                }
+        gen: GenType,
+        moves: &mut MoveList,
+    ) {
+        let tables = &*STEP_TABLES;
+        let sq = square(position_base);
+        let p = self.turn.value() as usize;
+        let push = |moves: &mut MoveList, to: Position| {
+            let target = self.chess_at(to);
+            if !target.belong_to(self.turn) && gen.accepts(target) {
+                moves.push(CompactMove::new(position_base, to));
             }
-            ChessType::Knight => {
-                if self.turn == Player::Red {
-                    if self.chess_at(position_base.up(1)) == Chess::None {
-                        targets.push(position_base.up(2).left(1));
-                        targets.push(position_base.up(2).right(1));
-                    }
-                    if self.chess_at(position_base.down(1)) == Chess::None {
-                        targets.push(position_base.down(2).left(1));
-                        targets.push(position_base.down(2).right(1));
-                    }
-                } else {
-                    if self.chess_at(position_base.down(1)) == Chess::None {
-                        targets.push(position_base.down(2).left(1));
-                        targets.push(position_base.down(2).right(1));
-                    }
-                    if self.chess_at(position_base.up(1)) == Chess::None {
-                        targets.push(position_base.up(2).left(1));
-                        targets.push(position_base.up(2).right(1));
-                    }
-                }
-
-                if self.chess_at(position_base.left(1)) == Chess::None {
-                    targets.push(position_base.up(1).left(2));
-                    targets.push(position_base.down(1).left(2));
-                }
-                if self.chess_at(position_base.right(1)) == Chess::None {
-                    targets.push(position_base.up(1).right(2));
-                    targets.push(position_base.down(1).right(2));
-                }
-            }
-            ChessType::Rook => {
-                if self.turn == Player::Red {
-                    for delta in 1..(position_base.row + 1) {
-                        targets.push(position_base.up(delta));
-                        if self.chess_at(position_base.up(delta)) != Chess::None {
-                            break;
-                        }
-                    }
-                    for delta in 1..(BOARD_HEIGHT - position_base.row) {
-                        targets.push(position_base.down(delta));
-                        if self.chess_at(position_base.down(delta)) != Chess::None {
-                            break;
-                        }
-                    }
-                } else {
-                    for delta in 1..(position_base.row + 1) {
-                        targets.push(position_base.up(delta));
-                        if self.chess_at(position_base.up(delta)) != Chess::None {
-                            break;
-                        }
-                    }
-                    for delta in 1..(BOARD_HEIGHT - position_base.row) {
-                        targets.push(position_base.down(delta));
-                        if self.chess_at(position_base.down(delta)) != Chess::None {
+        };
+        let steps = match chess.chess_type() {
+            Some(ChessType::King) => &tables.king[p][sq],
+            Some(ChessType::Advisor) => &tables.advisor[p][sq],
+            Some(ChessType::Bishop) => &tables.bishop[p][sq],
+            Some(ChessType::Knight) => &tables.knight[sq],
+            Some(ChessType::Pawn) => &tables.pawn[p][sq],
+            Some(ChessType::Rook) => {
+                for (dr, dc) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                    let mut pos = Position::new(position_base.row + dr, position_base.col + dc);
+                    while in_board(pos) {
+                        push(moves, pos);
+                        if self.chess_at(pos) != Chess::None {
                             break;
                         }
+                        pos = Position::new(pos.row + dr, pos.col + dc);
                     }
                 }
-
-                for delta in 1..(position_base.col + 1) {
-                    targets.push(position_base.left(delta));
-                    if self.chess_at(position_base.left(delta)) != Chess::None {
-                        break;
-                    }
-                }
-                for delta in 1..(BOARD_WIDTH - position_base.col) {
-                    targets.push(position_base.right(delta));
-                    if self.chess_at(position_base.right(delta)) != Chess::None {
-                        break;
-                    }
-                }
+                return;
             }
-            ChessType::Cannon => {
-                let mut has_chess = false;
-                for delta in 1..(position_base.row + 1) {
-                    if !has_chess {
-                        if self.chess_at(position_base.up(delta)) != Chess::None {
-                            has_chess = true;
-                        } else {
-                            targets.push(position_base.up(delta));
-                        }
-                    } else if self.chess_at(position_base.up(delta)) != Chess::None {
-                        targets.push(position_base.up(delta));
-                        break;
-                    }
-                }
-                let mut has_chess = false;
-                for delta in 1..(BOARD_HEIGHT - position_base.row) {
-                    if !has_chess {
-                        if self.chess_at(position_base.down(delta)) != Chess::None {
-                            has_chess = true;
-                        } else {
-                            targets.push(position_base.down(delta));
-                        }
-                    } else if self.chess_at(position_base.down(delta)) != Chess::None {
-                        targets.push(position_base.down(delta));
-                        break;
-                    }
-                }
-                let mut has_chess = false;
-                for delta in 1..(position_base.col + 1) {
-                    if !has_chess {
-                        if self.chess_at(position_base.left(delta)) != Chess::None {
-                            has_chess = true;
-                        } else {
-                            targets.push(position_base.left(delta));
-                        }
-                    } else if self.chess_at(position_base.left(delta)) != Chess::None {
-                        targets.push(position_base.left(delta));
-                        break;
-                    }
-                }
-                let mut has_chess = false;
-                for delta in 1..(BOARD_WIDTH - position_base.col) {
-                    if !has_chess {
-                        if self.chess_at(position_base.right(delta)) != Chess::None {
-                            has_chess = true;
-                        } else {
-                            targets.push(position_base.right(delta));
+            Some(ChessType::Cannon) => {
+                for (dr, dc) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                    let mut pos = Position::new(position_base.row + dr, position_base.col + dc);
+                    let mut has_chess = false;
+                    while in_board(pos) {
+                        if !has_chess {
+                            if self.chess_at(pos) != Chess::None {
+                                has_chess = true;
+                            } else {
+                                push(moves, pos);
+                            }
+                        } else if self.chess_at(pos) != Chess::None {
+                            push(moves, pos);
+                            break;
                         }
-                    } else if self.chess_at(position_base.right(delta)) != Chess::None {
-                        targets.push(position_base.right(delta));
-                        break;
+                        pos = Position::new(pos.row + dr, pos.col + dc);
                     }
                 }
+                return;
             }
-            ChessType::Pawn => {
-                // 过河兵可以左右走
-                if !in_country(position_base.row, self.turn) {
-                    targets.push(position_base.left(1));
-                    targets.push(position_base.right(1));
-                }
-                if self.turn == Player::Black {
-                    targets.push(position_base.down(1))
-                } else {
-                    targets.push(position_base.up(1));
-                }
+            None => return,
+        };
+        for step in steps.iter() {
+            if step
+                .block
+                .is_none_or(|block| self.chess_at(block) == Chess::None)
+            {
+                push(moves, step.to);
             }
         }
-        targets
     }
-    pub fn generate_move(&mut self, capture_only: bool) -> Vec<Move> {
+    pub fn generate_move(&mut self, capture_only: bool) -> MoveList {
+        self.generate_moves(if capture_only {
+            GenType::Captures
+        } else {
+            GenType::All
+        })
+    }
+    pub fn generate_moves(&mut self, gen: GenType) -> MoveList {
+        if cfg!(feature = "bitboard") {
+            self.generate_move_bitboard(gen)
+        } else {
+            self.generate_move_mailbox(gen)
+        }
+    }
+    pub fn generate_move_mailbox(&mut self, gen: GenType) -> MoveList {
         self.gen_counter += 1;
-        let mut moves = vec![];
+        let mut moves = MoveList::new();
         for i in 0..BOARD_HEIGHT {
             for j in 0..BOARD_WIDTH {
                 let position_base = Position::new(i, j);
                 // 遍历每个行棋方的棋
                 let chess = self.chess_at(position_base);
                 if chess.belong_to(self.turn) {
-                    if let Some(ct) = chess.chess_type() {
-                        let targets = self.generate_move_for_chess_type(ct, position_base);
-                        let move_base = Move {
-                            player: self.turn,
-                            from: position_base,
-                            to: position_base,
-                            chess,
-                            capture: Chess::None,
-                        };
-                        for target in targets {
-                            let valid = if ct == ChessType::King || ct == ChessType::Advisor {
-                                // 帅和士要在九宫格内
-                                in_palace(target, self.turn)
-                            } else if ct == ChessType::Bishop {
-                                // 象不能过河
-                                in_country(target.row, self.turn) && in_board(target)
-                            } else {
-                                in_board(target)
-                            };
-
-                            if valid {
-                                if !self.chess_at(target).belong_to(self.turn)
-                                    && (!capture_only
-                                        || self.chess_at(target).chess_type().is_some())
-                                {
-                                    moves
-                                        .push(move_base.with_target(target, self.chess_at(target)));
-                                }
-                            }
-                        }
-                    }
+                    self.generate_move_for_chess(chess, position_base, gen, &mut moves);
                 }
             }
         }
-        moves.sort_by(|a, b| {
-            (self.chess_at(b.to).value() - self.chess_at(b.from).value())
-                .cmp(&(self.chess_at(a.to).value() - self.chess_at(a.from).value()))
-        });
+        sort_moves(self, &mut moves);
         moves
     }
-    // 简单的评价，双方每个棋子的子力之和的差
-    pub fn evaluate(&self, player: Player) -> i32 {
-        let mut red_score = 0;
-        let mut black_score = 0;
-        for i in 0..BOARD_HEIGHT as usize {
-            for j in 0..BOARD_WIDTH as usize {
-                let chess = self.chess_at(Position::new(i as i32, j as i32));
-                if let Some(ct) = chess.chess_type() {
-                    let pos = if chess.belong_to(Player::Black) {
-                        Position::new(i as i32, j as i32).flip()
-                    } else {
-                        Position::new(i as i32, j as i32)
-                    };
-                    let score = match ct {
-                        ChessType::King => KING_VALUE_TABLE[pos.row as usize][pos.col as usize],
-                        ChessType::Advisor => {
-                            ADVISOR_VALUE_TABLE[pos.row as usize][pos.col as usize]
-                        }
-                        ChessType::Bishop => BISHOP_VALUE_TABLE[pos.row as usize][pos.col as usize],
-                        ChessType::Knight => KNIGHT_VALUE_TABLE[pos.row as usize][pos.col as usize],
-                        ChessType::Rook => ROOK_VALUE_TABLE[pos.row as usize][pos.col as usize],
-                        ChessType::Cannon => CANNON_VALUE_TABLE[pos.row as usize][pos.col as usize],
-                        ChessType::Pawn => PAWN_VALUE_TABLE[pos.row as usize][pos.col as usize],
-                    };
-                    if chess.belong_to(Player::Black) {
-                        black_score += score
-                    } else {
-                        red_score += score
-                    }
+    // 重新扫描整个棋盘计算双方的分值，只在初始化和校验增量更新时使用
+    pub fn calc_values(&self) -> [i32; 2] {
+        let mut values = [0, 0];
+        for i in 0..BOARD_HEIGHT {
+            for j in 0..BOARD_WIDTH {
+                let pos = Position::new(i, j);
+                let chess = self.chess_at(pos);
+                if let Some(player) = chess.player() {
+                    values[player.value() as usize] += position_value(chess, pos);
                 }
             }
         }
-        if player == Player::Red {
-            red_score - black_score + INITIATIVE_BONUS
-        } else {
-            black_score - red_score + INITIATIVE_BONUS
-        }
+        values
+    }
+    // 检查增量更新的分值和重新扫描的结果一致。每次评价都扫描太慢，只在测试里调用
+    #[cfg(test)]
+    pub fn assert_values(&self) {
+        assert_eq!(self.values, self.calc_values(), "{}", self.to_fen());
+    }
+    // 简单的评价，双方每个棋子的子力之和的差
+    pub fn evaluate(&self, player: Player) -> i32 {
+        self.values[player.value() as usize] - self.values[player.next().value() as usize]
+            + INITIATIVE_BONUS
+    }
+    // 局面的哈希值，包含轮到哪一方走。键值由固定的HASH_SEED生成，不受ZOBRIST_SEED影响，
+    // 不同进程之间保持一致，可以保存到开局库等文件里。每次都重新计算，搜索里用zobrist_value
+    pub fn hash(&self) -> u64 {
+        HASH_ZOBRIST.calc_board(&self.chesses, self.turn)
     }
     pub fn find_record(&self) -> Option<Record> {
-        if let Some(record) =
-            &self.records[(self.zobrist_value & (RECORD_SIZE - 1) as u64) as usize]
+        if let Some(Some(record)) = self
+            .records
+            .get((self.zobrist_value & (RECORD_SIZE - 1) as u64) as usize)
         {
-            if record.zobrist_lock == self.zobrist_value_lock && self.turn == record.turn {
+            if record.zobrist_lock == self.zobrist_value_lock {
                 Some(record.clone())
             } else {
                 None
@@ -953,6 +995,20 @@ impl Board {
             self.records[(self.zobrist_value & (RECORD_SIZE - 1) as u64) as usize] = Some(record);
         }
     }
+    pub fn history_value(&self, m: CompactMove) -> i32 {
+        self.history[history_index(m.source(), m.target())]
+    }
+    // 不吃子的着法产生截断时，记入杀手表和历史表
+    fn update_quiet_cutoff(&mut self, m: &Move, depth: i32) {
+        self.history[history_index(m.from, m.to)] += depth * depth;
+        let m = CompactMove::from(m);
+        if let Some(killers) = self.killers.get_mut(self.distance as usize) {
+            if killers[0] != Some(m) {
+                killers[1] = killers[0];
+                killers[0] = Some(m);
+            }
+        }
+    }
     pub fn alpha_beta_pvs(&mut self, depth: i32, mut alpha: i32, beta: i32) -> (i32, Option<Move>) {
         // if let Some(record) = self.find_record() {
         //     if record.depth <= depth {
@@ -965,43 +1021,42 @@ impl Board {
         }
         let mut count = 0; // 记录尝试了多少种着法
 
-        // 优先尝试迭代深度搜索的上一层搜索结果
-        let mut moves = self.generate_move(false);
+        // 优先尝试置换表里的着法
+        let mut hash_move = self.find_record().and_then(|r| r.best_move);
         // 如果符合上次搜索的着法线路，那么优先按此线路搜索下去
         for (i, m) in self.best_moves_last.iter().enumerate() {
             if let Some(ml) = self.move_history.get(i) {
-                if m != ml {
+                if *m != CompactMove::from(ml) {
                     break;
                 }
             } else {
-                moves.insert(0, m.clone());
+                hash_move = Some(*m);
                 break;
             }
         }
+        let killers = self
+            .killers
+            .get(self.distance as usize)
+            .copied()
+            .unwrap_or([None; 2]);
+        let mut picker = MovePicker::new(hash_move, killers);
         let mut best_move = None;
-        for m in moves {
+        while let Some(m) = picker.next(self) {
             self.do_move(&m);
-            if self.is_checked(self.turn.next()) {
-                self.undo_move(&m);
-                continue;
-            }
-            count = count + 1;
+            count += 1;
             // 先使用0宽窗口进行搜索
-            let (v, bmt) = self.alpha_beta_pvs(depth - 1, -(alpha + 1), -alpha);
+            let (v, _) = self.alpha_beta_pvs(depth - 1, -(alpha + 1), -alpha);
 
             let mut best_value = -v;
-            let mut bm = bmt;
             if best_value == MIN || (best_value > alpha && best_value < beta) {
-                let (v, bmt) = self.alpha_beta_pvs(depth - 1, -beta, -alpha);
+                let (v, _) = self.alpha_beta_pvs(depth - 1, -beta, -alpha);
                 // self.add_record(Record {
                 //     value: -v,
                 //     depth,
                 //     best_move: bmt.clone(),
                 //     zobrist_lock: self.zobrist_value_lock,
-                //     turn: self.turn,
                 // });
                 best_value = -v;
-                bm = bmt;
             }
 
             // let (v, bmt) = self.alpha_beta(depth - 1, -beta, -alpha);
@@ -1010,11 +1065,14 @@ impl Board {
 
             if best_value >= beta {
                 self.undo_move(&m);
+                if m.capture == Chess::None {
+                    self.update_quiet_cutoff(&m, depth);
+                }
                 return (best_value, None);
             }
             if best_value > alpha {
                 alpha = best_value;
-                best_move = Some(m.clone());
+                best_move = Some(m);
             }
 
             self.undo_move(&m);
@@ -1023,7 +1081,7 @@ impl Board {
         // 如果尝试的着法数为0,说明已经被绝杀
         // 深度减分，深度越小，说明越早被将死，局面分应该越低，由于depth是递减的，
         // 所以深度越小，depth越大，减去depth的局面分就越低
-        return (if count == 0 { KILL - depth } else { alpha }, best_move);
+        (if count == 0 { KILL - depth } else { alpha }, best_move)
     }
     pub fn quies(&mut self, mut alpha: i32, beta: i32) -> i32 {
         if self.distance > MAX_DEPTH {
@@ -1036,17 +1094,15 @@ impl Board {
         if v > alpha {
             alpha = v
         }
-        let moves = if self.is_checked(self.turn.next()) {
-            self.generate_move(false)
+        // 被将军时生成所有应将着法，否则只搜索吃子
+        let moves = if self.is_checked(self.turn) {
+            self.generate_legal_move(GenType::All)
         } else {
-            self.generate_move(true)
+            self.generate_legal_move(GenType::Captures)
         };
         for m in moves {
+            let m = m.to_move(self);
             self.do_move(&m);
-            if self.is_checked(self.turn.next()) {
-                self.undo_move(&m);
-                continue;
-            }
             let v = -self.quies(-beta, -alpha);
             self.undo_move(&m);
             if v >= beta {
@@ -1056,24 +1112,33 @@ impl Board {
                 alpha = v;
             }
         }
-        return alpha;
+        alpha
     }
     pub fn iterative_deepening(&mut self, max_depth: i32) -> (i32, Option<Move>) {
+        self.iterative_deepening_with(max_depth, |_, _, _, _| {})
+    }
+    // 每搜索完一层，用(局面, 深度, 分数, 最佳着法)调用一次report
+    pub fn iterative_deepening_with(
+        &mut self,
+        max_depth: i32,
+        mut report: impl FnMut(&Board, i32, i32, Option<Move>),
+    ) -> (i32, Option<Move>) {
         if max_depth > 3 {
             for depth in 3..max_depth + 1 {
                 // self.records = vec![RECORD_NONE; RECORD_SIZE as usize];
                 let (v, bm) = self.alpha_beta_pvs(depth, MIN, MAX);
+                report(self, depth, v, bm);
                 if depth == max_depth {
-                    println!("第{}层: {:?}", depth, bm);
                     return (v, bm);
                 }
-                self.best_moves_last = vec![];
-                self.best_moves_last.reverse();
-                println!("第{}层: {:?}", depth, self.best_moves_last);
+                // 下一层先搜索这一层找到的最佳着法
+                self.best_moves_last = bm.iter().map(CompactMove::from).collect();
             }
         } else {
             // self.records = vec![RECORD_NONE; RECORD_SIZE as usize];
-            return self.alpha_beta_pvs(max_depth, MIN, MAX);
+            let (v, bm) = self.alpha_beta_pvs(max_depth, MIN, MAX);
+            report(self, max_depth, v, bm);
+            return (v, bm);
         }
         (0, None)
     }
@@ -1082,7 +1147,7 @@ impl Board {
 #[test]
 fn test_generate_move() {
     let mut board = Board::init();
-    for i in 0..1000000 {
+    for _ in 0..1000000 {
         board.generate_move(false);
     }
     assert_eq!(
@@ -1092,8 +1157,8 @@ fn test_generate_move() {
 }
 #[test]
 fn test_is_checked() {
-    let mut board = Board::init();
-    for i in 0..10000000 {
+    let board = Board::init();
+    for _ in 0..10000000 {
         board.is_checked(Player::Red);
     }
     assert_eq!(
@@ -1104,7 +1169,7 @@ fn test_is_checked() {
 #[test]
 fn test_move_and_unmove() {
     let mut board = Board::init();
-    for i in 0..800000 {
+    for _ in 0..800000 {
         let m = Move {
             player: Player::Red,
             from: Position::new(0, 0),
@@ -1113,7 +1178,7 @@ fn test_move_and_unmove() {
             capture: Chess::None,
         };
         board.apply_move(&m);
-        board.undo_move(&m);
+        board.unapply_move(&m);
     }
     assert_eq!(
         Board::init().generate_move(false).len(),
@@ -1131,12 +1196,53 @@ fn test_evaluate() {
         chess: Chess::Red(ChessType::Rook),
         capture: Chess::None,
     });
-    for i in 0..1000000 {
+    for _ in 0..1000000 {
         board.evaluate(Player::Red);
     }
     assert_eq!(board.evaluate(Player::Red), 7);
 }
 
+#[test]
+fn test_incremental_values() {
+    let mut board =
+        Board::from_fen("r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1")
+            .unwrap();
+    let initial = board.values;
+    let moves = board.generate_move(false);
+    for m in &moves {
+        let m = &m.to_move(&board);
+        board.do_move(m);
+        board.assert_values();
+        for reply in board.generate_move(false) {
+            let reply = reply.to_move(&board);
+            board.do_move(&reply);
+            board.assert_values();
+            board.undo_move(&reply);
+        }
+        board.undo_move(m);
+        assert_eq!(board.values, initial);
+    }
+}
+
+#[test]
+fn test_search_bookkeeping() {
+    // 搜索回到根节点时distance和move_history要复原，根节点以下各层都要记下杀手着法
+    let mut board =
+        Board::from_fen("r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1")
+            .unwrap();
+    let m = board.legal_moves()[0];
+    board.do_move(&m);
+    let fen = board.to_fen();
+    board.iterative_deepening(4);
+    assert_eq!(board.distance, 1);
+    assert_eq!(board.move_history, [m]);
+    assert_eq!(board.to_fen(), fen);
+    board.assert_values();
+    for distance in 2..5 {
+        assert!(board.killers[distance][0].is_some(), "{}", distance);
+    }
+}
+
 #[test]
 fn test_alpha_beta_pvs() {
     // println!("{:?}", Board::init().alpha_beta_pvs(1, MIN, MAX));
@@ -1154,14 +1260,154 @@ fn test_alpha_beta_pvs() {
 #[test]
 fn test_from_fen() {
     let fen = "rnb1kabnr/4a4/1c5c1/p1p3p2/4N4/8p/P1P3P1P/2C4C1/9/RNBAKAB1R w - - 0 1 moves e5d7";
-    println!("{:?}", Board::from_fen(fen).chesses);
+    println!(
+        "{}",
+        Board::from_fen(fen)
+            .unwrap()
+            .render(&crate::render::RenderOptions::default())
+    );
+}
+
+#[test]
+fn test_to_fen() {
+    assert_eq!(
+        Board::init().to_fen(),
+        "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1"
+    );
+    let fen = "2bak4/4a4/4b4/9/9/9/9/9/4A4/3K1R3 b - - 12 30";
+    assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+    // r和w都表示红方走
+    let board = Board::from_fen("4k4/9/9/9/9/9/9/9/9/4K4 r - - 0 1").unwrap();
+    assert_eq!(board.turn, Player::Red);
+    assert_eq!(board.to_fen(), "4k4/9/9/9/9/9/9/9/9/4K4 w - - 0 1");
+
+    // 走子和撤销后，回合数和吃子计数跟着变化
+    let mut board = Board::init();
+    let mut moves = vec![];
+    for iccs in ["h2e2", "h9g7", "e2e6", "g6g5", "e6i6"] {
+        let m = CompactMove::from_iccs(iccs).unwrap().to_move(&board);
+        board.apply_move(&m);
+        moves.push(m);
+    }
+    assert_eq!(
+        board.to_fen(),
+        "rnbakab1r/9/1c4nc1/p1p5C/6p2/9/P1P1P1P1P/1C7/9/RNBAKABNR b - - 0 3"
+    );
+    board.unapply_move(&moves.pop().unwrap());
+    assert_eq!(
+        board.to_fen(),
+        "rnbakab1r/9/1c4nc1/p1p1C3p/6p2/9/P1P1P1P1P/1C7/9/RNBAKABNR w - - 1 3"
+    );
+    while let Some(m) = moves.pop() {
+        board.unapply_move(&m);
+    }
+    assert_eq!(board.to_fen(), Board::init().to_fen());
+}
+
+#[test]
+fn test_fen_errors() {
+    let start = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR";
+    let parse = |fen: &str| Board::from_fen(fen).err();
+    assert_eq!(parse(""), Some(FenError::Empty));
+    assert_eq!(parse("4k4/9/9 w"), Some(FenError::RowCount(3)));
+    assert_eq!(
+        parse("4k5/9/9/9/9/9/9/9/9/4K4 w"),
+        Some(FenError::RowLength(0))
+    );
+    assert_eq!(
+        parse("4k4/9/9/9/9/9/9/9/9/4K3 w"),
+        Some(FenError::RowLength(9))
+    );
+    assert_eq!(
+        parse("4k4/9/9/9/9/9/9/9/9/4KKKKKK w"),
+        Some(FenError::RowLength(9))
+    );
+    assert_eq!(
+        parse("4x4/9/9/9/9/9/9/9/9/4K4 w"),
+        Some(FenError::InvalidPiece('x'))
+    );
+    assert_eq!(parse(start), Some(FenError::MissingSide));
+    assert_eq!(
+        parse(&format!("{} x", start)),
+        Some(FenError::InvalidSide("x".to_owned()))
+    );
+    assert_eq!(
+        parse(&format!("{} w - - a 1", start)),
+        Some(FenError::InvalidCounter("a".to_owned()))
+    );
+    assert_eq!(parse(&format!("{} w", start)), None);
+    assert_eq!("a0".parse::<Position>(), Ok(Position::new(9, 0)));
+    assert_eq!(
+        "k0".parse::<Position>(),
+        Err(MoveParseError::InvalidSquare("k0".to_owned()))
+    );
+    assert_eq!(
+        "a10".parse::<Position>(),
+        Err(MoveParseError::InvalidLength("a10".to_owned()))
+    );
+}
+
+#[test]
+fn test_fen_round_trip_book() {
+    let book = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/BOOK.DAT")).unwrap();
+    let mut count = 0;
+    for line in book.lines().filter(|l| !l.is_empty()) {
+        let fen = line.splitn(3, ' ').nth(2).unwrap();
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(
+            Board::from_fen(&board.to_fen()).unwrap().hash(),
+            board.hash()
+        );
+        count += 1;
+    }
+    assert!(count > 0);
 }
 
 #[test]
 fn test_king_eye_to_eye() {
-    let board = Board::from_fen("rnbakabnr/9/1c5c1/9/9/9/9/1C5C1/9/RNBAKABNR w - - 0 1");
-    println!("{:?}", board.chesses);
+    let board = Board::from_fen("rnbakabnr/9/1c5c1/9/9/9/9/1C5C1/9/RNBAKABNR w - - 0 1").unwrap();
+    println!("{}", board.render(&crate::render::RenderOptions::default()));
     println!("{}", board.king_eye_to_eye());
     let board = Board::init();
     println!("{}", board.king_eye_to_eye());
 }
+
+#[test]
+fn test_hash() {
+    let start = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR";
+    let board = Board::from_fen(&format!("{} w - - 0 1", start)).unwrap();
+    assert_eq!(board.hash(), Board::init().hash());
+    // 哈希值只由HASH_SEED决定，设置了ZOBRIST_SEED也不变，可以写进文件
+    assert_eq!(board.hash(), 0x5978_15b2_e659_41b8);
+    assert_ne!(
+        board.hash(),
+        Board::from_fen(&format!("{} b - - 0 1", start))
+            .unwrap()
+            .hash()
+    );
+    // 不同的着法顺序到达同一局面，哈希值相同
+    let play = |moves: &[&str]| {
+        let mut board = Board::init();
+        for m in moves {
+            let m = CompactMove::from_iccs(m).unwrap().to_move(&board);
+            board.apply_move(&m);
+        }
+        board
+    };
+    let a = play(&["h2e2", "h9g7", "b0c2"]);
+    let b = play(&["b0c2", "h9g7", "h2e2"]);
+    assert_eq!(a.hash(), b.hash());
+    assert_eq!(a.zobrist_value_lock, b.zobrist_value_lock);
+    // 同样的棋子，轮到的一方不同，哈希值不同
+    let c = Board::init();
+    let d = play(&["a0a1", "h9g7", "a1a2", "g7h9", "a2a0"]);
+    assert_eq!(c.chesses, d.chesses);
+    assert_ne!(c.hash(), d.hash());
+    assert_eq!(
+        a.hash(),
+        Board::from_fen("rnbakab1r/9/1c4nc1/p1p1p1p1p/9/9/P1P1P1P1P/1CN1C4/9/R1BAKABNR b - - 0 1")
+            .unwrap()
+            .hash()
+    );
+}
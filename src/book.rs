@@ -0,0 +1,229 @@
+use std::{fs, path::Path};
+
+use crate::{board::Board, compact::CompactMove, error::BookError};
+
+// 二进制开局库：16字节文件头，后面是按键值排好序的12字节记录，全部小端。
+// 键值就是Board::hash，不受ZOBRIST_SEED影响
+// 文件头：魔数NCBK、版本号u16、记录长度u16、记录数u32、保留u32
+// 记录：局面键值u64、压缩着法u16、权重u16
+pub const MAGIC: &[u8; 4] = b"NCBK";
+pub const VERSION: u16 = 1;
+const HEADER_SIZE: usize = 16;
+const RECORD_SIZE: usize = 12;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BookRecord {
+    pub key: u64,
+    pub m: CompactMove,
+    pub weight: u16,
+}
+
+// 只保存记录部分的原始字节，查找时直接在字节上二分
+#[derive(Default)]
+pub struct Book {
+    data: Vec<u8>,
+}
+
+impl Book {
+    // 相同局面的记录保持原来的先后顺序
+    pub fn new(mut records: Vec<BookRecord>) -> Self {
+        records.sort_by_key(|r| r.key);
+        let mut data = Vec::with_capacity(records.len() * RECORD_SIZE);
+        for r in records {
+            data.extend_from_slice(&r.key.to_le_bytes());
+            data.extend_from_slice(&r.m.0.to_le_bytes());
+            data.extend_from_slice(&r.weight.to_le_bytes());
+        }
+        Book { data }
+    }
+    // 文本格式每行是“着法 权重 FEN”
+    pub fn from_text(text: &str) -> Result<Self, BookError> {
+        let mut records = vec![];
+        for (i, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let mut tokens = line.splitn(3, ' ');
+            let (m, weight, fen) = match (tokens.next(), tokens.next(), tokens.next()) {
+                (Some(m), Some(weight), Some(fen)) => (m, weight, fen),
+                _ => return Err(BookError::MissingField(i + 1)),
+            };
+            let m = CompactMove::from_iccs(m).map_err(|e| BookError::Move(i + 1, e))?;
+            let weight = weight
+                .parse::<u16>()
+                .map_err(|_| BookError::InvalidWeight(i + 1, weight.to_owned()))?;
+            let board = Board::from_fen(fen).map_err(|e| BookError::Fen(i + 1, e))?;
+            records.push(BookRecord {
+                key: board.hash(),
+                m,
+                weight,
+            });
+        }
+        Ok(Book::new(records))
+    }
+    pub fn from_bytes(mut bytes: Vec<u8>) -> Result<Self, BookError> {
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+            return Err(BookError::InvalidFormat);
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let count = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize;
+        if u16_at(4) != VERSION
+            || u16_at(6) as usize != RECORD_SIZE
+            || bytes.len() != HEADER_SIZE + count * RECORD_SIZE
+        {
+            return Err(BookError::InvalidFormat);
+        }
+        bytes.drain(..HEADER_SIZE);
+        let book = Book { data: bytes };
+        // 没排好序的文件没法二分查找
+        if (1..book.len()).any(|i| book.key(i - 1) > book.key(i)) {
+            return Err(BookError::InvalidFormat);
+        }
+        // 着法的起点和终点都要在棋盘上
+        if (0..book.len()).any(|i| {
+            let m = book.record(i).m.0;
+            m & 0x7f >= 90 || m >> 7 & 0x7f >= 90
+        }) {
+            return Err(BookError::InvalidFormat);
+        }
+        Ok(book)
+    }
+    // 按文件头判断是二进制格式还是文本格式
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BookError> {
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(MAGIC) {
+            Book::from_bytes(bytes)
+        } else {
+            let text = String::from_utf8_lossy(&bytes);
+            Book::from_text(&text)
+        }
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.data.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(RECORD_SIZE as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), BookError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+    pub fn len(&self) -> usize {
+        self.data.len() / RECORD_SIZE
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    fn key(&self, i: usize) -> u64 {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(&self.data[i * RECORD_SIZE..i * RECORD_SIZE + 8]);
+        u64::from_le_bytes(buf)
+    }
+    pub fn record(&self, i: usize) -> BookRecord {
+        let r = &self.data[i * RECORD_SIZE..(i + 1) * RECORD_SIZE];
+        BookRecord {
+            key: self.key(i),
+            m: CompactMove(u16::from_le_bytes([r[8], r[9]])),
+            weight: u16::from_le_bytes([r[10], r[11]]),
+        }
+    }
+    // 二分找到第一条键值不小于key的记录，相同局面的记录连在一起
+    pub fn lookup(&self, board: &Board) -> Vec<BookRecord> {
+        let key = board.hash();
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.key(mid) < key {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        (lo..self.len())
+            .take_while(|&i| self.key(i) == key)
+            .map(|i| self.record(i))
+            .collect()
+    }
+}
+
+// 把文本开局库转成二进制格式，返回记录数
+pub fn convert<P: AsRef<Path>, Q: AsRef<Path>>(text: P, binary: Q) -> Result<usize, BookError> {
+    let book = Book::from_text(&fs::read_to_string(text)?)?;
+    book.save(binary)?;
+    Ok(book.len())
+}
+
+#[test]
+fn test_binary_book() {
+    let text = "h2e2 9 rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1\n\
+                h9g7 5 rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR b - - 1 1\n\
+                b0c2 4 rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1\n";
+    let book = Book::from_text(text).unwrap();
+    assert_eq!(book.len(), 3);
+    let moves = book
+        .lookup(&Board::init())
+        .iter()
+        .map(|r| (r.m.to_iccs(), r.weight))
+        .collect::<Vec<(String, u16)>>();
+    assert_eq!(moves, [("h2e2".to_owned(), 9), ("b0c2".to_owned(), 4)]);
+    let empty = Board::from_fen("4k4/9/9/9/9/9/9/9/9/4K4 w - - 0 1").unwrap();
+    assert!(book.lookup(&empty).is_empty());
+
+    // 写出再读回来完全一样
+    let bytes = book.to_bytes();
+    assert_eq!(bytes.len(), 16 + 3 * 12);
+    assert_eq!(&bytes[..8], b"NCBK\x01\x00\x0c\x00");
+    let loaded = Book::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(loaded.data, book.data);
+    assert!(matches!(
+        Book::from_bytes(bytes[..bytes.len() - 1].to_vec()),
+        Err(BookError::InvalidFormat)
+    ));
+    // 第一条记录的终点改成不存在的格子
+    let mut corrupted = bytes.clone();
+    corrupted[16 + 8..16 + 10].copy_from_slice(&(95u16 << 7).to_le_bytes());
+    assert!(matches!(
+        Book::from_bytes(corrupted),
+        Err(BookError::InvalidFormat)
+    ));
+    assert!(matches!(
+        Book::from_text(
+            "h2e2 -1 rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1"
+        ),
+        Err(BookError::InvalidWeight(1, _))
+    ));
+
+    // 轮到哪一方走也算在键值里
+    assert_ne!(
+        Board::init().hash(),
+        Board::from_fen(&Board::init().to_fen().replace(" w ", " b "))
+            .unwrap()
+            .hash()
+    );
+}
+
+#[test]
+fn test_convert_book() {
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("chess_ai_book_{}.bin", std::process::id()));
+    let count = convert(concat!(env!("CARGO_MANIFEST_DIR"), "/BOOK.DAT"), &path).unwrap();
+    let book = Book::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(book.len(), count);
+    // 随程序发布的BOOK.BIN要和文本库保持一致
+    let shipped = Book::open(concat!(env!("CARGO_MANIFEST_DIR"), "/BOOK.BIN")).unwrap();
+    assert_eq!(shipped.data, book.data);
+    assert!(!book.lookup(&Board::init()).is_empty());
+    // 文本库里的每一条都能在二进制库里找到
+    let text = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/BOOK.DAT")).unwrap();
+    for line in text.lines().step_by(97) {
+        let mut tokens = line.splitn(3, ' ');
+        let m = CompactMove::from_iccs(tokens.next().unwrap()).unwrap();
+        let board = Board::from_fen(tokens.nth(1).unwrap()).unwrap();
+        assert!(book.lookup(&board).iter().any(|r| r.m == m));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{
+    board::{Board, ChessType, Move, Player},
+    error::MoveParseError,
+    notation::{Direction, Which},
+};
+
+const CHINESE_NUMBERS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+fn chess_name(chess: ChessType, player: Player) -> char {
+    match (chess, player) {
+        (ChessType::King, Player::Red) => '帅',
+        (ChessType::King, Player::Black) => '将',
+        (ChessType::Advisor, Player::Red) => '仕',
+        (ChessType::Advisor, Player::Black) => '士',
+        (ChessType::Bishop, Player::Red) => '相',
+        (ChessType::Bishop, Player::Black) => '象',
+        (ChessType::Knight, _) => '马',
+        (ChessType::Rook, _) => '车',
+        (ChessType::Cannon, _) => '炮',
+        (ChessType::Pawn, Player::Red) => '兵',
+        (ChessType::Pawn, Player::Black) => '卒',
+    }
+}
+
+// 红方用中文数字，黑方用阿拉伯数字
+fn number(n: i32, player: Player) -> char {
+    match player {
+        Player::Red => CHINESE_NUMBERS[n as usize - 1],
+        Player::Black => (b'0' + n as u8) as char,
+    }
+}
+
+// 同一纵线上的前、中、后，四五个兵卒时用一到五
+fn order(index: usize, count: usize) -> char {
+    match (index, count) {
+        (0, 2..=3) => '前',
+        (1, 3) => '中',
+        (_, 2..=3) => '后',
+        _ => CHINESE_NUMBERS[index],
+    }
+}
+
+// 把各种写法统一成一种，方便比较：繁体字、红黑双方的不同名称、全角数字等
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '帅' | '帥' | '将' | '將' => '帅',
+            '仕' | '士' => '仕',
+            '相' | '象' => '相',
+            '马' | '馬' | '傌' => '马',
+            '车' | '車' | '俥' => '车',
+            '炮' | '砲' | '包' => '炮',
+            '兵' | '卒' => '兵',
+            '进' | '進' => '进',
+            '后' | '後' => '后',
+            '１'..='９' => (b'1' + (c as u32 - '１' as u32) as u8) as char,
+            _ => match CHINESE_NUMBERS.iter().position(|&n| n == c) {
+                Some(i) => (b'1' + i as u8) as char,
+                None => c,
+            },
+        })
+        .collect()
+}
+
+impl Board {
+    // 中文纵线记谱，例如炮二平五、马8进7，按走子前的局面
+    pub fn to_chinese(&self, m: &Move) -> String {
+        let d = self.describe(m);
+        let name = chess_name(d.chess, d.player);
+        let mut s = String::new();
+        match d.which {
+            Which::File(file) => {
+                s.push(name);
+                s.push(number(file, d.player));
+            }
+            Which::Order(index, count) => {
+                s.push(order(index, count));
+                s.push(name);
+            }
+            Which::OrderFile(index, count, file) => {
+                s.push(order(index, count));
+                s.push(number(file, d.player));
+            }
+        }
+        s.push(match d.direction {
+            Direction::Advance => '进',
+            Direction::Retreat => '退',
+            Direction::Traverse => '平',
+        });
+        s.push(number(d.target, d.player));
+        s
+    }
+    // 解析中文纵线记谱，双方的棋子名称和数字写法都可以接受
+    pub fn parse_chinese(&mut self, s: &str) -> Result<Move, MoveParseError> {
+        let normalized = normalize(s);
+        if normalized.chars().count() != 4 {
+            return Err(MoveParseError::InvalidLength(s.to_owned()));
+        }
+        self.legal_moves()
+            .into_iter()
+            .find(|m| normalize(&self.to_chinese(m)) == normalized)
+            .ok_or_else(|| MoveParseError::NoSuchMove(s.to_owned()))
+    }
+}
+
+#[test]
+fn test_chinese() {
+    use crate::compact::CompactMove;
+
+    let play = |board: &mut Board, iccs: &str, chinese: &str| {
+        let m = CompactMove::from_iccs(iccs).unwrap().to_move(board);
+        assert_eq!(board.to_chinese(&m), chinese);
+        assert_eq!(board.parse_chinese(chinese), Ok(m));
+        board.apply_move(&m);
+    };
+    let mut board = Board::init();
+    play(&mut board, "h2e2", "炮二平五");
+    play(&mut board, "h9g7", "马8进7");
+    play(&mut board, "h0g2", "马二进三");
+    play(&mut board, "i9h9", "车9平8");
+    play(&mut board, "i0h0", "车一平二");
+    play(&mut board, "b7b3", "炮2进4");
+    play(&mut board, "e3e4", "兵五进一");
+    play(&mut board, "c9e7", "象3进5");
+    play(&mut board, "f0e1", "仕四进五");
+    play(&mut board, "d9e8", "士4进5");
+    play(&mut board, "e2d2", "炮五平六");
+    play(&mut board, "b3b5", "炮2退2");
+
+    // 繁体字、全角数字和另一方的棋子名称
+    let mut board = Board::init();
+    let m = CompactMove::from_iccs("h2e2").unwrap().to_move(&board);
+    assert_eq!(board.parse_chinese("砲２平５"), Ok(m));
+    assert_eq!(board.parse_chinese("炮 二 平 五"), Ok(m));
+    assert_eq!(
+        board.parse_chinese("马二平三"),
+        Err(MoveParseError::NoSuchMove("马二平三".to_owned()))
+    );
+    assert_eq!(
+        board.parse_chinese("炮二平"),
+        Err(MoveParseError::InvalidLength("炮二平".to_owned()))
+    );
+}
+
+#[test]
+fn test_chinese_tandem() {
+    use crate::compact::CompactMove;
+
+    let check = |fen: &str, iccs: &str, chinese: &str| {
+        let mut board = Board::from_fen(fen).unwrap();
+        let m = CompactMove::from_iccs(iccs).unwrap().to_move(&board);
+        assert_eq!(board.to_chinese(&m), chinese);
+        assert_eq!(board.parse_chinese(chinese), Ok(m));
+    };
+    // 同一纵线上的两个车
+    let fen = "3k5/9/9/9/9/9/9/R8/9/R3K4 w - - 0 1";
+    check(fen, "a2a5", "前车进三");
+    check(fen, "a0b0", "后车平八");
+    // 黑方的前后从黑方看
+    let fen = "3k5/9/9/9/9/9/4p4/4p4/9/4K4 b - - 0 1";
+    check(fen, "e2e1", "前卒进1");
+    check(fen, "e3d3", "后卒平4");
+    // 三个兵在同一纵线上
+    let fen = "3k5/9/2P6/2P6/2P6/9/9/9/9/4K4 w - - 0 1";
+    check(fen, "c7c8", "前兵进一");
+    check(fen, "c6d6", "中兵平六");
+    check(fen, "c5b5", "后兵平八");
+    // 两条纵线上都有重叠的兵，用纵线号代替兵字
+    let fen = "3k5/9/9/2P1P4/2P1P4/9/9/9/9/4K4 w - - 0 1";
+    check(fen, "c6c7", "前七进一");
+    check(fen, "e5f5", "后五平四");
+    // 四个兵在同一纵线上
+    let fen = "3k5/9/2P6/2P6/2P6/2P6/9/9/9/4K4 w - - 0 1";
+    check(fen, "c7c8", "一兵进一");
+    check(fen, "c5b5", "三兵平八");
+    // 同一纵线上的仕和相仍然用纵线号，进退已经能区分
+    let fen = "4k4/9/9/9/9/9/9/3A5/9/3AK4 w - - 0 1";
+    check(fen, "d2e1", "仕六退五");
+    check(fen, "d0e1", "仕六进五");
+    let fen = "4k4/9/9/9/9/2B6/9/9/9/2B1K4 w - - 0 1";
+    check(fen, "c0e2", "相七进五");
+    check(fen, "c4e2", "相七退五");
+    let fen = "2b1k4/9/9/9/2b6/9/9/9/9/4K4 b - - 0 1";
+    check(fen, "c9e7", "象3进5");
+    check(fen, "c5e7", "象3退5");
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::{
+    bitboard::{position, square},
+    board::{Board, Move, Position},
+    error::MoveParseError,
+};
+
+// 16位压缩着法，低7位是起点，高7位是终点，格子编号为row*9+col。
+// 搜索、置换表和杀手表里只保存这个，需要时再结合局面还原成Move
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CompactMove(pub u16);
+
+impl CompactMove {
+    pub fn new(from: Position, to: Position) -> Self {
+        CompactMove(square(from) as u16 | (square(to) as u16) << 7)
+    }
+    pub fn source(self) -> Position {
+        position((self.0 & 0x7F) as usize)
+    }
+    pub fn target(self) -> Position {
+        position((self.0 >> 7 & 0x7F) as usize)
+    }
+    // 结合当前局面还原出走的子和吃的子
+    pub fn to_move(self, board: &Board) -> Move {
+        let chess = board.chess_at(self.source());
+        Move {
+            player: chess.player().unwrap_or(board.turn),
+            from: self.source(),
+            to: self.target(),
+            chess,
+            capture: board.chess_at(self.target()),
+        }
+    }
+    // 解析ICCS坐标格式，例如h2e2
+    pub fn from_iccs(iccs: &str) -> Result<Self, MoveParseError> {
+        if iccs.len() != 4 || !iccs.is_char_boundary(2) {
+            return Err(MoveParseError::InvalidLength(iccs.to_owned()));
+        }
+        let square = |s: &str| {
+            s.parse::<Position>()
+                .map_err(|_| MoveParseError::InvalidSquare(iccs.to_owned()))
+        };
+        Ok(CompactMove::new(square(&iccs[..2])?, square(&iccs[2..])?))
+    }
+    pub fn to_iccs(self) -> String {
+        format!("{}{}", self.source(), self.target())
+    }
+}
+
+impl From<&Move> for CompactMove {
+    fn from(m: &Move) -> Self {
+        CompactMove::new(m.from, m.to)
+    }
+}
+
+impl From<Move> for CompactMove {
+    fn from(m: Move) -> Self {
+        CompactMove::new(m.from, m.to)
+    }
+}
+
+impl fmt::Display for CompactMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.source(), self.target())
+    }
+}
+
+#[test]
+fn test_compact_move() {
+    let mut board = Board::init();
+    for m in board.legal_moves() {
+        let compact = CompactMove::from(&m);
+        assert_eq!(compact.to_move(&board), m);
+        assert_eq!(CompactMove::from_iccs(&compact.to_iccs()), Ok(compact));
+    }
+    let m = CompactMove::from_iccs("h2e2").unwrap();
+    assert_eq!(m.source(), Position::new(7, 7));
+    assert_eq!(m.target(), Position::new(7, 4));
+    assert_eq!(m.to_string(), "h2e2");
+    assert_eq!(
+        CompactMove::from_iccs("j2e2"),
+        Err(MoveParseError::InvalidSquare("j2e2".to_owned()))
+    );
+    assert_eq!(
+        CompactMove::from_iccs("h2e"),
+        Err(MoveParseError::InvalidLength("h2e".to_owned()))
+    );
+    assert_eq!(std::mem::size_of::<CompactMove>(), 2);
+}
@@ -7,6 +7,20 @@ pub const KILL: i32 = MIN + 100;
 pub const MAX: i32 = 99999;
 pub const RECORD_SIZE: i32 = 0x1FFFFE;
 pub const MAX_DEPTH: i32 = 64;
+// 连续60回合没有吃子判和，按半回合计
+pub const MOVE_LIMIT: i32 = 120;
+// 搜索用的Zobrist键值的默认种子，可以用环境变量ZOBRIST_SEED覆盖，只影响置换表
+pub const ZOBRIST_SEED: u64 = 0x4E43_6865_7373_2021;
+// Board::hash用的键值种子，不受ZOBRIST_SEED影响。
+// 开局库等文件里保存的都是这个哈希值，改了种子就要重新生成
+pub const HASH_SEED: u64 = 0x4E43_426F_6F6B_0001;
+
+fn zobrist_seed() -> u64 {
+    std::env::var("ZOBRIST_SEED")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(ZOBRIST_SEED)
+}
 
 lazy_static! {
     pub static ref FEN_MAP: HashMap<char, Chess> = HashMap::from([
@@ -25,6 +39,8 @@ lazy_static! {
         ('C', Chess::Red(ChessType::Cannon)),
         ('P', Chess::Red(ChessType::Pawn)),
     ]);
-    pub static ref ZOBRIST_TABLE: Zobristable = Zobristable::new();
-    pub static ref ZOBRIST_TABLE_LOCK: Zobristable = Zobristable::new();
+    pub static ref ZOBRIST_TABLE: Zobristable = Zobristable::new(zobrist_seed());
+    // 校验用的键值，种子取反以便和上面的不同
+    pub static ref ZOBRIST_TABLE_LOCK: Zobristable = Zobristable::new(!zobrist_seed());
+    pub static ref HASH_ZOBRIST: Zobristable = Zobristable::new(HASH_SEED);
 }
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+
+use crate::{
+    board::{Board, Chess, Player, Position, BOARD_HEIGHT, BOARD_WIDTH},
+    error::{DhtmlXqError, FenError},
+    pgn::{GameResult, PgnGame, PgnLine, PgnMove},
+    xqf::PIECES,
+};
+
+// DhtmlXQ的块名和对应的PGN标签
+const TAGS: [(&str, &str); 8] = [
+    ("title", "Title"),
+    ("event", "Event"),
+    ("date", "Date"),
+    ("place", "Site"),
+    ("red", "Red"),
+    ("black", "Black"),
+    ("open", "Opening"),
+    ("author", "Annotator"),
+];
+const RESULTS: [(&str, GameResult); 4] = [
+    ("红胜", GameResult::RedWin),
+    ("黑胜", GameResult::BlackWin),
+    ("和棋", GameResult::Draw),
+    ("未知", GameResult::Unknown),
+];
+
+// 格子是两个数字，先列后行，行从黑方底线数起，和Position一样
+fn parse_square(s: &[u8]) -> Option<Position> {
+    match s {
+        [col @ b'0'..=b'8', row @ b'0'..=b'9'] => {
+            Some(Position::new((row - b'0') as i32, (col - b'0') as i32))
+        }
+        _ => None,
+    }
+}
+
+fn format_square(pos: Position) -> String {
+    format!("{}{}", pos.col, pos.row)
+}
+
+fn parse_moves(s: &str) -> Result<Vec<(Position, Position)>, DhtmlXqError> {
+    let invalid = || DhtmlXqError::InvalidMoveList(s.to_owned());
+    let s = s.trim();
+    if !s.len().is_multiple_of(4) {
+        return Err(invalid());
+    }
+    s.as_bytes()
+        .chunks(4)
+        .map(|m| {
+            Ok((
+                parse_square(&m[..2]).ok_or_else(invalid)?,
+                parse_square(&m[2..]).ok_or_else(invalid)?,
+            ))
+        })
+        .collect()
+}
+
+// 取出所有[DhtmlXQ_xxx]...[/DhtmlXQ_xxx]块
+fn blocks(text: &str) -> HashMap<String, String> {
+    let mut blocks = HashMap::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[DhtmlXQ_") {
+        let after = &rest[start + "[DhtmlXQ_".len()..];
+        let name_end = match after.find(']') {
+            Some(i) => i,
+            None => break,
+        };
+        let name = &after[..name_end];
+        let body = &after[name_end + 1..];
+        let close = format!("[/DhtmlXQ_{}]", name);
+        match body.find(&close) {
+            Some(end) => {
+                blocks.insert(name.to_owned(), body[..end].to_owned());
+                rest = &body[end + close.len()..];
+            }
+            None => rest = body,
+        }
+    }
+    blocks
+}
+
+// 主线的注释是commentN，变着的是comment分支号_N，N是从开局算起的第几步
+fn comment_key(branch: usize, ply: usize) -> String {
+    if branch == 0 {
+        format!("comment{}", ply)
+    } else {
+        format!("comment{}_{}", branch, ply)
+    }
+}
+
+// 32个棋子的位置，不在棋盘上的是99
+fn binit(board: &Board) -> String {
+    let mut squares = vec![None; 32];
+    for row in 0..BOARD_HEIGHT {
+        for col in 0..BOARD_WIDTH {
+            let pos = Position::new(row, col);
+            let chess = board.chess_at(pos);
+            if let (Some(player), Some(chess_type)) = (chess.player(), chess.chess_type()) {
+                let base = if player == Player::Red { 0 } else { 16 };
+                if let Some(i) =
+                    (0..16).find(|&i| PIECES[i] == chess_type && squares[base + i].is_none())
+                {
+                    squares[base + i] = Some(pos);
+                }
+            }
+        }
+    }
+    squares
+        .into_iter()
+        .map(|pos| pos.map_or("99".to_owned(), format_square))
+        .collect()
+}
+
+// 变着的分支，move_父分支_第几步_分支号
+struct Branches<'a> {
+    moves: HashMap<usize, Vec<(Position, Position)>>,
+    children: HashMap<(usize, usize), Vec<usize>>,
+    blocks: &'a HashMap<String, String>,
+}
+
+impl<'a> Branches<'a> {
+    fn new(blocks: &'a HashMap<String, String>) -> Result<Self, DhtmlXqError> {
+        let mut moves = HashMap::new();
+        let mut children = HashMap::new();
+        let movelist = blocks.get("movelist").map_or("", |s| s.as_str());
+        moves.insert(0, parse_moves(movelist)?);
+        for (name, value) in blocks {
+            let numbers = match name.strip_prefix("move_") {
+                Some(rest) => rest
+                    .split('_')
+                    .map(|n| n.parse::<usize>())
+                    .collect::<Result<Vec<usize>, _>>(),
+                None => continue,
+            };
+            // 分支号总比父分支大，避免循环
+            if let Ok(&[parent, ply, branch]) = numbers.as_deref() {
+                if parent < branch && ply > 0 {
+                    moves.insert(branch, parse_moves(value)?);
+                    children
+                        .entry((parent, ply))
+                        .or_insert_with(Vec::new)
+                        .push(branch);
+                }
+            }
+        }
+        for branches in children.values_mut() {
+            branches.sort_unstable();
+        }
+        Ok(Branches {
+            moves,
+            children,
+            blocks,
+        })
+    }
+    fn comment(&self, branch: usize, ply: usize) -> Option<String> {
+        self.blocks
+            .get(&comment_key(branch, ply))
+            .map(|c| c.trim().replace("||", "\n"))
+            .filter(|c| !c.is_empty())
+    }
+    // 分支的第一步是开局后的第start步，返回时局面恢复原样
+    fn line(
+        &self,
+        branch: usize,
+        start: usize,
+        board: &mut Board,
+    ) -> Result<PgnLine, DhtmlXqError> {
+        let mut line = PgnLine::default();
+        let moves = self.moves.get(&branch).map_or(&[][..], |m| m.as_slice());
+        for (k, &(from, to)) in moves.iter().enumerate() {
+            let ply = start + k;
+            let m = board
+                .legal_moves()
+                .into_iter()
+                .find(|m| m.from == from && m.to == to)
+                .ok_or_else(|| {
+                    DhtmlXqError::IllegalMove(format_square(from) + &format_square(to))
+                })?;
+            let mut pm = PgnMove::new(m);
+            pm.comment = self.comment(branch, ply);
+            for &child in self.children.get(&(branch, ply)).into_iter().flatten() {
+                pm.variations.push(self.line(child, ply, board)?);
+            }
+            board.apply_move(&m);
+            line.moves.push(pm);
+        }
+        for pm in line.moves.iter().rev() {
+            board.unapply_move(&pm.m);
+        }
+        Ok(line)
+    }
+}
+
+// 按深度优先的顺序给变着编号，branches[i]是第i个分支的(父分支, 第几步, 着法)
+fn write_branch(
+    line: &PgnLine,
+    branch: usize,
+    start: usize,
+    branches: &mut Vec<(usize, usize, String)>,
+    comments: &mut Vec<(String, String)>,
+) {
+    let mut moves = String::new();
+    for (k, pm) in line.moves.iter().enumerate() {
+        let ply = start + k;
+        moves.push_str(&format_square(pm.m.from));
+        moves.push_str(&format_square(pm.m.to));
+        if let Some(comment) = &pm.comment {
+            comments.push((comment_key(branch, ply), comment.replace('\n', "||")));
+        }
+        for variation in &pm.variations {
+            let child = branches.len();
+            branches.push((branch, ply, String::new()));
+            write_branch(variation, child, ply, branches, comments);
+        }
+    }
+    branches[branch].2 = moves;
+}
+
+impl PgnGame {
+    // 读取网页上的[DhtmlXQ]棋谱
+    pub fn from_dhtmlxq(text: &str) -> Result<Self, DhtmlXqError> {
+        let blocks = blocks(text);
+        let mut game = PgnGame::default();
+        for (block, name) in TAGS.iter() {
+            if let Some(value) = blocks.get(*block).map(|v| v.trim()) {
+                if !value.is_empty() {
+                    game.set_tag(name, value);
+                }
+            }
+        }
+        game.result = blocks
+            .get("result")
+            .and_then(|r| RESULTS.iter().find(|(s, _)| *s == r.trim()))
+            .map_or(GameResult::Unknown, |&(_, result)| result);
+        game.set_tag("Result", &game.result.to_string());
+
+        let branches = Branches::new(&blocks)?;
+        let mut board = match blocks.get("binit") {
+            Some(binit) if binit.trim().len() == 64 => {
+                let mut board = Board::empty();
+                for (i, sq) in binit.trim().as_bytes().chunks(2).enumerate() {
+                    if let Some(pos) = parse_square(sq) {
+                        let player = if i < 16 { Player::Red } else { Player::Black };
+                        board.set_chess(pos, Chess::of(player, PIECES[i % 16]));
+                    }
+                }
+                // 没有记录轮到哪一方走，用第一步的棋子判断
+                if let Some(&(from, _)) = branches.moves[&0].first() {
+                    board.turn = board.chess_at(from).player().unwrap_or(Player::Red);
+                }
+                board.validate().map_err(DhtmlXqError::InvalidPosition)?;
+                Board::from_fen(&board.to_fen()).unwrap()
+            }
+            _ => Board::init(),
+        };
+        let fen = board.to_fen();
+        if fen != Board::init().to_fen() {
+            game.set_tag("FEN", &fen);
+        }
+        game.mainline = branches.line(0, 1, &mut board)?;
+        game.mainline.comment = branches.comment(0, 0);
+        Ok(game)
+    }
+    // 写成[DhtmlXQ]棋谱，NAG和变着开头的注释没有对应的写法
+    pub fn to_dhtmlxq(&self) -> Result<String, FenError> {
+        let mut text = String::from("[DhtmlXQ]\n");
+        let mut block = |name: &str, value: &str| {
+            text.push_str(&format!("[DhtmlXQ_{0}]{1}[/DhtmlXQ_{0}]\n", name, value));
+        };
+        for (name, tag) in TAGS.iter() {
+            if let Some(value) = self.tag(tag) {
+                block(name, value);
+            }
+        }
+        let (result, _) = RESULTS.iter().find(|(_, r)| *r == self.result).unwrap();
+        block("result", result);
+        if self.tag("FEN").is_some() {
+            block("binit", &binit(&self.start_board()?));
+        }
+        let mut branches = vec![(0, 0, String::new())];
+        let mut comments = vec![];
+        if let Some(comment) = &self.mainline.comment {
+            comments.push((comment_key(0, 0), comment.replace('\n', "||")));
+        }
+        write_branch(&self.mainline, 0, 1, &mut branches, &mut comments);
+        block("movelist", &branches[0].2);
+        for (branch, (parent, ply, moves)) in branches.iter().enumerate().skip(1) {
+            block(&format!("move_{}_{}_{}", parent, ply, branch), moves);
+        }
+        for (key, comment) in comments {
+            block(&key, &comment);
+        }
+        text.push_str("[/DhtmlXQ]\n");
+        Ok(text)
+    }
+}
+
+#[test]
+fn test_dhtmlxq() {
+    use crate::compact::CompactMove;
+
+    let text = "[DhtmlXQ]
+[DhtmlXQ_title]测试对局[/DhtmlXQ_title]
+[DhtmlXQ_red]红方[/DhtmlXQ_red]
+[DhtmlXQ_result]红胜[/DhtmlXQ_result]
+[DhtmlXQ_movelist]7747706279678070[/DhtmlXQ_movelist]
+[DhtmlXQ_move_0_3_1]19271022[/DhtmlXQ_move_0_3_1]
+[DhtmlXQ_comment0]开局[/DhtmlXQ_comment0]
+[DhtmlXQ_comment1]中炮||最常见[/DhtmlXQ_comment1]
+[DhtmlXQ_comment1_4]屏风马[/DhtmlXQ_comment1_4]
+[/DhtmlXQ]";
+    let game = PgnGame::from_dhtmlxq(text).unwrap();
+    assert_eq!(game.tag("Title"), Some("测试对局"));
+    assert_eq!(game.tag("Red"), Some("红方"));
+    assert_eq!(game.result, GameResult::RedWin);
+    let iccs = |line: &PgnLine| {
+        line.moves
+            .iter()
+            .map(|pm| CompactMove::from(&pm.m).to_string())
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(iccs(&game.mainline), ["h2e2", "h9g7", "h0g2", "i9h9"]);
+    assert_eq!(game.mainline.comment.as_deref(), Some("开局"));
+    let moves = &game.mainline.moves;
+    assert_eq!(moves[0].comment.as_deref(), Some("中炮\n最常见"));
+    assert_eq!(iccs(&moves[2].variations[0]), ["b0c2", "b9c7"]);
+    assert_eq!(
+        moves[2].variations[0].moves[1].comment.as_deref(),
+        Some("屏风马")
+    );
+
+    let written = game.to_dhtmlxq().unwrap();
+    assert!(written.contains("[DhtmlXQ_movelist]7747706279678070[/DhtmlXQ_movelist]"));
+    assert!(written.contains("[DhtmlXQ_move_0_3_1]19271022[/DhtmlXQ_move_0_3_1]"));
+    assert_eq!(PgnGame::from_dhtmlxq(&written).unwrap(), game);
+
+    // PGN读进来的棋谱也能写成DhtmlXQ
+    let pgn = PgnGame::parse("1. H2-E2 H9-G7 (1... B9-C7) *").unwrap();
+    let written = pgn.to_dhtmlxq().unwrap();
+    assert!(written.contains("[DhtmlXQ_move_0_2_1]1022[/DhtmlXQ_move_0_2_1]"));
+    assert_eq!(
+        PgnGame::from_dhtmlxq(&written).unwrap().mainline,
+        pgn.mainline
+    );
+}
+
+#[test]
+fn test_dhtmlxq_position() {
+    // 残局，黑车先走
+    let binit = format!(
+        "{}49{}00{}30{}",
+        "99".repeat(4),
+        "99".repeat(11),
+        "99".repeat(3),
+        "99".repeat(11)
+    );
+    let text = format!(
+        "[DhtmlXQ_binit]{}[/DhtmlXQ_binit][DhtmlXQ_movelist]0001[/DhtmlXQ_movelist]",
+        binit
+    );
+    let game = PgnGame::from_dhtmlxq(&text).unwrap();
+    assert_eq!(game.tag("FEN"), Some("r2k5/9/9/9/9/9/9/9/9/4K4 b - - 0 1"));
+    assert_eq!(game.moves().len(), 1);
+    let written = game.to_dhtmlxq().unwrap();
+    assert!(written.contains(&format!("[DhtmlXQ_binit]{}[/DhtmlXQ_binit]", binit)));
+    assert_eq!(PgnGame::from_dhtmlxq(&written).unwrap(), game);
+
+    // 没有黑将
+    let text = text.replace(&binit, &binit.replacen("30", "99", 1));
+    assert_eq!(
+        PgnGame::from_dhtmlxq(&text),
+        Err(DhtmlXqError::InvalidPosition(vec![
+            crate::error::PositionError::MissingKing(Player::Black)
+        ]))
+    );
+
+    assert_eq!(
+        PgnGame::from_dhtmlxq("[DhtmlXQ_movelist]774[/DhtmlXQ_movelist]"),
+        Err(DhtmlXqError::InvalidMoveList("774".to_owned()))
+    );
+    assert_eq!(
+        PgnGame::from_dhtmlxq("[DhtmlXQ_movelist]77477746[/DhtmlXQ_movelist]"),
+        Err(DhtmlXqError::IllegalMove("7746".to_owned()))
+    );
+}
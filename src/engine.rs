@@ -1,96 +1,167 @@
 use regex::Regex;
-use std::io;
+use std::{io, path::Path, time::Instant};
 
-use crate::board::{Board, Move};
+use crate::{
+    board::{Board, Move},
+    book::Book,
+    compact::CompactMove,
+    error::{BookError, CommandError},
+    iccs::{IccsFormat, RankOrigin},
+    notation::Notation,
+    render::{Glyphs, RenderOptions},
+};
 
-#[derive(Debug)]
-pub struct PreLoad {
-    zobrist_value: u64,
-    zobrist_value_check: u64,
-    best_move: String,
-    weight: i32,
+// 同一局面有多个库着法时怎么选
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BookMode {
+    // 只走权重最大的
+    Best,
+    // 按权重随机
+    Weighted,
+    // 不管权重，等概率随机
+    Uniform,
+}
+
+// [0, n)之间的随机数
+fn random_below(n: u64) -> u64 {
+    let mut buf = [0; 8];
+    getrandom::getrandom(&mut buf).unwrap();
+    u64::from_be_bytes(buf) % n
 }
 
 // UCCI引擎
 pub struct UCCIEngine {
     pub board: Board,
-    pub book: Vec<PreLoad>,
+    pub book: Book,
+    // 上一条position指令出错，拒绝在这个局面上搜索
+    pub position_refused: bool,
+    pub notation: Notation,
+    // 收发ICCS坐标时的写法
+    pub iccs: IccsFormat,
+    // position指令里的最后一步，显示局面时标出来
+    pub last_move: Option<Move>,
+    pub book_mode: BookMode,
 }
 
 impl UCCIEngine {
-    pub fn new(book_path: Option<&str>) -> Self {
-        let mut book = vec![];
+    pub fn new(book_path: Option<&Path>) -> Result<Self, BookError> {
+        let mut book = Book::default();
         if let Some(path) = book_path {
-            for line in std::fs::read_to_string(path).unwrap().split("\n") {
-                if line.len() == 0 {
-                    continue;
-                }
-                let mut tokens = line.splitn(3, " ");
-                let m = tokens.next().unwrap();
-                let weight = tokens.next().unwrap();
-                let fen = tokens.next().unwrap();
-                let board = Board::from_fen(fen);
-                book.push(PreLoad {
-                    zobrist_value: board.zobrist_value,
-                    zobrist_value_check: board.zobrist_value_lock,
-                    best_move: m.to_owned(),
-                    weight: weight.parse::<i32>().unwrap(),
-                });
-            }
-            book.sort_by(|a, b| a.zobrist_value.cmp(&b.zobrist_value));
+            book = Book::open(path)?;
             println!("加载开局库完成，共加载{}个局面", book.len());
-            println!("{:?}", book[1000]);
         }
-        UCCIEngine {
+        Ok(UCCIEngine {
             board: Board::init(),
             book,
-        }
+            position_refused: false,
+            notation: Notation::Iccs,
+            iccs: IccsFormat::default(),
+            last_move: None,
+            book_mode: BookMode::Weighted,
+        })
     }
-    pub fn search_in_book(&self) -> Option<String> {
-        let candidates = self
+    // 当前局面的所有库着法和权重
+    pub fn book_moves(&mut self) -> Vec<(CompactMove, i32)> {
+        let moves = self
             .book
-            .binary_search_by(|probe| probe.zobrist_value.cmp(&self.board.zobrist_value))
-            .map(|i| &self.book[i])
+            .lookup(&self.board)
             .into_iter()
-            .filter(|x| x.zobrist_value_check == self.board.zobrist_value_lock)
-            .collect::<Vec<&PreLoad>>();
-        if candidates.len() > 0 {
-            let mut buf = [0; 4];
-            getrandom::getrandom(&mut buf).unwrap();
-            let index = i32::from_be_bytes(buf) % candidates.len() as i32;
-            Some(candidates[index as usize].best_move.clone())
-        } else {
-            None
+            .map(|r| (r.m, r.weight as i32))
+            .collect::<Vec<(CompactMove, i32)>>();
+        let moves = self.legal_book_moves(moves);
+        if !moves.is_empty() {
+            return moves;
+        }
+        // 库里没有时再找左右镜像的局面，着法镜像回来
+        let moves = self
+            .book
+            .lookup(&self.board.mirror_lr())
+            .into_iter()
+            .map(|r| (r.m.mirror_lr(), r.weight as i32))
+            .collect();
+        self.legal_book_moves(moves)
+    }
+    // 键值碰撞或者库文件有错时，库里的着法在当前局面可能走不了
+    fn legal_book_moves(&mut self, mut moves: Vec<(CompactMove, i32)>) -> Vec<(CompactMove, i32)> {
+        moves.retain(|&(m, _)| {
+            let m = m.to_move(&self.board);
+            self.board.is_legal(&m)
+        });
+        moves
+    }
+    pub fn search_in_book(&mut self) -> Option<CompactMove> {
+        let moves = self.book_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        let total = moves.iter().map(|&(_, w)| w.max(0) as u64).sum::<u64>();
+        match self.book_mode {
+            // 权重相同时取库里靠前的
+            BookMode::Best => moves.iter().rev().max_by_key(|&&(_, w)| w).map(|&(m, _)| m),
+            BookMode::Weighted if total > 0 => {
+                let mut r = random_below(total);
+                moves
+                    .iter()
+                    .find(|&&(_, w)| {
+                        let w = w.max(0) as u64;
+                        if r < w {
+                            true
+                        } else {
+                            r -= w;
+                            false
+                        }
+                    })
+                    .map(|&(m, _)| m)
+            }
+            // 权重都不大于0时也等概率随机
+            BookMode::Weighted | BookMode::Uniform => {
+                Some(moves[random_below(moves.len() as u64) as usize].0)
+            }
         }
     }
 
     pub fn start(&mut self) {
         loop {
             let mut cmd = String::new();
-            io::stdin().read_line(&mut cmd).unwrap();
-            cmd = cmd.replace("\n", "");
+            // 读到输入结束或者出错时退出
+            match io::stdin().read_line(&mut cmd) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let cmd = cmd.trim();
             if cmd == "quit" {
                 break;
             }
-            let mut token = cmd.splitn(2, " ");
-            let cmd = token.next().unwrap();
-            match cmd {
-                "ucci" => self.info(),
-                "isready" => self.is_ready(),
-                "position" => self.position(token.next().unwrap()),
-                "go" => {
-                    self.go(token
-                        .next()
-                        .unwrap()
-                        .split(" ")
-                        .last()
-                        .unwrap()
-                        .parse()
-                        .unwrap());
-                }
-                _ => println!("not support"),
+            if let Err(e) = self.execute(cmd) {
+                println!("{}", e);
+            }
+        }
+    }
+
+    // 执行一条指令，出错时不改变引擎状态
+    pub fn execute(&mut self, cmd: &str) -> Result<(), CommandError> {
+        let mut token = cmd.splitn(2, ' ');
+        let param = token.next().unwrap_or("");
+        let args = token.next().unwrap_or("").trim();
+        match param {
+            "ucci" => self.info(),
+            "isready" => self.is_ready(),
+            "setoption" => self.set_option(args)?,
+            "position" => self.position(args)?,
+            "perft" => self.perft(args)?,
+            "fen" => println!("{}", self.board.to_fen()),
+            "d" => self.display(args)?,
+            "go" => {
+                let depth = args
+                    .rsplit(' ')
+                    .next()
+                    .and_then(|d| d.parse().ok())
+                    .ok_or_else(|| CommandError::InvalidArgument(cmd.to_owned()))?;
+                self.go(depth);
             }
+            _ => return Err(CommandError::Unsupported(cmd.to_owned())),
         }
+        Ok(())
     }
 
     pub fn info(&self) {
@@ -99,6 +170,10 @@ impl UCCIEngine {
         println!("id author nealian");
         println!("id user 2021-2022 www.nealian.cn");
         println!("option usemillisec type check");
+        println!("option notation type combo var iccs var chinese var wxf default iccs");
+        println!("option rankorigin type combo var bottom var top default bottom");
+        println!("option iccsstyle type combo var lower var upper var dash default lower");
+        println!("option bookmode type combo var best var weighted var uniform default weighted");
         println!("ucciok");
     }
 
@@ -106,43 +181,135 @@ impl UCCIEngine {
         println!("readyok");
     }
 
-    pub fn position(&mut self, param: &str) {
+    // setoption <选项> <值>
+    pub fn set_option(&mut self, param: &str) -> Result<(), CommandError> {
+        let mut tokens = param.split_whitespace();
+        match (tokens.next(), tokens.next()) {
+            (Some("notation"), Some("iccs")) => self.notation = Notation::Iccs,
+            (Some("notation"), Some("chinese")) => self.notation = Notation::Chinese,
+            (Some("notation"), Some("wxf")) => self.notation = Notation::Wxf,
+            (Some("rankorigin"), Some("bottom")) => self.iccs.origin = RankOrigin::Bottom,
+            (Some("rankorigin"), Some("top")) => self.iccs.origin = RankOrigin::Top,
+            // lower为h2e2，upper为H2E2，dash为H2-E2
+            (Some("iccsstyle"), Some("lower")) => {
+                self.iccs.upper_case = false;
+                self.iccs.dash = false;
+            }
+            (Some("iccsstyle"), Some("upper")) => {
+                self.iccs.upper_case = true;
+                self.iccs.dash = false;
+            }
+            (Some("iccsstyle"), Some("dash")) => {
+                self.iccs.upper_case = true;
+                self.iccs.dash = true;
+            }
+            (Some("bookmode"), Some("best")) => self.book_mode = BookMode::Best,
+            (Some("bookmode"), Some("weighted")) => self.book_mode = BookMode::Weighted,
+            (Some("bookmode"), Some("uniform")) => self.book_mode = BookMode::Uniform,
+            (Some("notation"), _)
+            | (Some("rankorigin"), _)
+            | (Some("iccsstyle"), _)
+            | (Some("bookmode"), _) => return Err(CommandError::InvalidArgument(param.to_owned())),
+            // 界面可能会发送其他不支持的选项，忽略即可
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // d [chinese|ascii] [color] [nocoord]，调试时显示当前局面
+    pub fn display(&self, param: &str) -> Result<(), CommandError> {
+        let mut options = RenderOptions {
+            last_move: self.last_move,
+            ..RenderOptions::default()
+        };
+        for token in param.split_whitespace() {
+            match token {
+                "chinese" => options.glyphs = Glyphs::Chinese,
+                "ascii" => options.glyphs = Glyphs::Ascii,
+                "color" => options.color = true,
+                "nocoord" => options.coordinates = false,
+                _ => return Err(CommandError::InvalidArgument(param.to_owned())),
+            }
+        }
+        println!("{}", self.board.render(&options));
+        println!("fen {}", self.board.to_fen());
+        Ok(())
+    }
+
+    pub fn position(&mut self, param: &str) -> Result<(), CommandError> {
+        match UCCIEngine::parse_position(param, &self.iccs) {
+            Ok((board, last_move)) => {
+                self.board = board;
+                self.last_move = last_move;
+                self.position_refused = false;
+                Ok(())
+            }
+            Err(e) => {
+                self.position_refused = true;
+                Err(e)
+            }
+        }
+    }
+    fn parse_position(
+        param: &str,
+        format: &IccsFormat,
+    ) -> Result<(Board, Option<Move>), CommandError> {
         let regex = Regex::new(
-            r#"^(?:fen (?P<fen>[kabnrcpKABNRCP1-9/]+ [wrb] - - \d+ \d+)|(?P<startpos>startpos))(?: moves (?P<moves>[a-i]\d[a-i]\d(?: [a-i]\d[a-i]\d)*))?$"#,
+            r#"^(?:fen (?P<fen>[^ ]+ [^ ]+(?: [^ ]+ [^ ]+ [^ ]+ [^ ]+)?)|(?P<startpos>startpos))(?: moves (?P<moves>[^ ]+(?: [^ ]+)*))?$"#,
         ).unwrap();
-        for captures in regex.captures_iter(param) {
-            if let Some(fen) = captures.name("fen") {
-                self.board = Board::from_fen(fen.as_str());
-            }
-            if let Some(_) = captures.name("startpos") {
-                self.board = Board::init();
-            }
-            if let Some(moves) = captures.name("moves") {
-                for m in moves.as_str().split(" ") {
-                    let (from, to) = m.split_at(2);
-                    self.board.apply_move(&Move {
-                        player: self.board.turn,
-                        from: from.into(),
-                        to: to.into(),
-                        chess: self.board.chess_at(from.into()),
-                        capture: self.board.chess_at(to.into()),
-                    });
+        let captures = regex
+            .captures(param)
+            .ok_or_else(|| CommandError::InvalidArgument(param.to_owned()))?;
+        let mut board = match captures.name("fen") {
+            Some(fen) => {
+                let board = Board::from_fen(fen.as_str())?;
+                board.validate().map_err(CommandError::InvalidPosition)?;
+                board
+            }
+            None => Board::init(),
+        };
+        let mut last_move = None;
+        if let Some(moves) = captures.name("moves") {
+            for (i, iccs) in moves.as_str().split(' ').enumerate() {
+                let m = format.parse(iccs)?;
+                if !board
+                    .legal_moves()
+                    .iter()
+                    .any(|l| CompactMove::from(l) == m)
+                {
+                    return Err(CommandError::IllegalMove(i + 1, iccs.to_owned()));
                 }
+                let m = m.to_move(&board);
+                board.apply_move(&m);
+                last_move = Some(m);
             }
         }
+        Ok((board, last_move))
     }
     pub fn go(&mut self, depth: i32) {
+        if self.position_refused {
+            println!("nobestmove");
+            return;
+        }
         if let Some(m) = self.search_in_book() {
-            println!("bestmove {}", m);
+            println!("bestmove {}", self.iccs.format(m));
             return;
         }
-        let (value, best_move) = self.board.iterative_deepening(depth);
+        let notation = self.notation;
+        let iccs = self.iccs;
+        let (value, best_move) =
+            self.board
+                .iterative_deepening_with(depth, |board, depth, value, best_move| {
+                    if let Some(m) = best_move {
+                        let pv = notation.format(board, &m, &iccs);
+                        println!("info depth {} score {} pv {}", depth, value, pv);
+                    }
+                });
         if let Some(m) = best_move {
             if m.is_valid() {
                 println!(
-                    "bestmove {}{} value {}",
-                    m.from.to_string(),
-                    m.to.to_string(),
+                    "bestmove {} value {}",
+                    self.iccs.format(CompactMove::from(m)),
                     value
                 );
                 return;
@@ -150,34 +317,295 @@ impl UCCIEngine {
         }
         println!("nobestmove");
     }
+    // perft [divide] <depth>，统计当前局面的合法着法节点数
+    pub fn perft(&mut self, param: &str) -> Result<(), CommandError> {
+        let mut tokens = param.split_whitespace();
+        let (divide, depth) = match tokens.next() {
+            Some("divide") => (true, tokens.next()),
+            depth => (false, depth),
+        };
+        let depth = match depth {
+            Some(d) => d
+                .parse()
+                .map_err(|_| CommandError::InvalidArgument(param.to_owned()))?,
+            None => 1,
+        };
+        let start = Instant::now();
+        let nodes = if divide {
+            let mut nodes = 0;
+            for (m, n) in self.board.divide(depth) {
+                println!("{}: {}", self.iccs.format(CompactMove::from(m)), n);
+                nodes += n;
+            }
+            nodes
+        } else {
+            self.board.perft(depth)
+        };
+        println!(
+            "perft {} nodes {} time {}ms",
+            depth,
+            nodes,
+            start.elapsed().as_millis()
+        );
+        Ok(())
+    }
     pub fn quit() {
         println!("bye");
     }
 }
 #[test]
 fn test_ucci_engine() {
-    let mut engine = UCCIEngine::new(None);
+    let mut engine = UCCIEngine::new(None).unwrap();
     engine.info();
     engine.is_ready();
-    engine.position(
-        "fen rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1 moves b2d2 b9a7 a9a8 h7h0 b0a2 a8d8 a0b0 d8d2 b0b7 d2h2 b7g7 h9g7 g3g4 i9h9",
+    // 第3步又是黑方走子，整条着法序列被拒绝
+    assert_eq!(
+        engine.position(
+            "fen rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1 moves b2d2 b9a7 a9a8 h7h0 b0a2 a8d8 a0b0 d8d2 b0b7 d2h2 b7g7 h9g7 g3g4 i9h9",
+        ),
+        Err(CommandError::IllegalMove(3, "a9a8".to_owned()))
     );
+    engine
+        .position(
+            "fen rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1 moves b2d2 b9a7",
+        )
+        .unwrap();
     // engine.position("startpos moves b0c2");
     engine.go(6);
-    println!("{:?}", engine.board.chesses);
+    println!("{}", engine.board.render(&RenderOptions::default()));
     println!("{} {}", engine.board.gen_counter, engine.board.counter);
 }
 
+#[test]
+fn test_perft_command() {
+    let mut engine = UCCIEngine::new(None).unwrap();
+    engine.position("startpos moves h2e2").unwrap();
+    engine.perft("divide 2").unwrap();
+    engine.perft("3").unwrap();
+}
+
 #[test]
 fn test_kill() {
-    let mut engine = UCCIEngine::new(None);
+    let mut engine = UCCIEngine::new(None).unwrap();
     engine.info();
     engine.is_ready();
-    engine.position("fen 4k4/9/9/9/9/9/9/4p4/9/5K3 b - - 0 1");
+    engine
+        .position("fen 4k4/9/9/9/9/9/9/4p4/9/5K3 b - - 0 1")
+        .unwrap();
     // engine.position("startpos moves b0c2");
     let moves = engine.board.generate_move(false);
     println!("{:?}", moves);
-    println!("{:?}", engine.board.chesses);
+    println!("{}", engine.board.render(&RenderOptions::default()));
     engine.go(8);
     println!("{} {}", engine.board.gen_counter, engine.board.counter);
 }
+
+#[test]
+fn test_bad_input() {
+    let mut engine = UCCIEngine::new(None).unwrap();
+    engine.execute("position startpos moves h2e2").unwrap();
+    let fen = engine.board.to_fen();
+    assert_eq!(
+        engine.execute("go"),
+        Err(CommandError::InvalidArgument("go".to_owned()))
+    );
+    assert_eq!(
+        engine.execute("position fen rnbakabnr/9/1c5c1 w - - 0 1"),
+        Err(CommandError::Fen(crate::error::FenError::RowCount(3)))
+    );
+    assert_eq!(
+        engine.execute("position startpos moves h2e2 h9x7"),
+        Err(CommandError::Move(
+            crate::error::MoveParseError::InvalidSquare("h9x7".to_owned())
+        ))
+    );
+    assert_eq!(
+        engine.execute("position fen 4k4/9/9/9/9/9/9/9/9/4K4 w - - 0 1"),
+        Err(CommandError::InvalidPosition(vec![
+            crate::error::PositionError::FacingKings
+        ]))
+    );
+    assert!(engine.execute("position").is_err());
+    assert!(engine.position_refused);
+    assert!(engine.execute("perft x").is_err());
+    assert!(engine.execute("hello").is_err());
+    // 出错的指令不改变当前局面
+    assert_eq!(engine.board.to_fen(), fen);
+    assert!(matches!(
+        UCCIEngine::new(Some(Path::new("no/such/book"))),
+        Err(BookError::Io(_))
+    ));
+}
+
+#[test]
+fn test_display_command() {
+    let mut engine = UCCIEngine::new(None).unwrap();
+    engine.execute("d").unwrap();
+    assert_eq!(engine.last_move, None);
+    engine.execute("position startpos moves h2e2 h9g7").unwrap();
+    let last_move = engine.last_move.unwrap();
+    assert_eq!(CompactMove::from(&last_move).to_string(), "h9g7");
+    engine.execute("d ascii color nocoord").unwrap();
+    assert_eq!(
+        engine.execute("d big"),
+        Err(CommandError::InvalidArgument("big".to_owned()))
+    );
+}
+
+#[test]
+fn test_illegal_moves() {
+    let mut engine = UCCIEngine::new(None).unwrap();
+    // 走对方的子、走空格、走法不对、送将
+    for (moves, index, iccs) in [
+        ("h7e7", 1, "h7e7"),
+        ("h2e2 e5e4", 2, "e5e4"),
+        ("h2e2 h9g7 b0b1", 3, "b0b1"),
+    ] {
+        assert_eq!(
+            engine.execute(&format!("position startpos moves {}", moves)),
+            Err(CommandError::IllegalMove(index, iccs.to_owned()))
+        );
+        assert!(engine.position_refused);
+        assert_eq!(engine.board.to_fen(), Board::init().to_fen());
+    }
+    // 马离开后将帅照面
+    let fen = "position fen 4k4/9/9/9/9/9/9/9/4N4/4K4 w - - 0 1 moves";
+    assert_eq!(
+        engine.execute(&format!("{} e1d3", fen)),
+        Err(CommandError::IllegalMove(1, "e1d3".to_owned()))
+    );
+    assert_eq!(
+        engine.execute(&format!("{} e0d0 e9f9 e1f3 f9e9 f3e5 e9d9", fen)),
+        Err(CommandError::IllegalMove(6, "e9d9".to_owned()))
+    );
+    engine
+        .execute("position startpos moves h2e2 h9g7 e2e6 e9e8")
+        .unwrap();
+    assert!(!engine.position_refused);
+}
+
+#[test]
+fn test_notation_option() {
+    let mut engine = UCCIEngine::new(None).unwrap();
+    let m = CompactMove::from_iccs("h2e2")
+        .unwrap()
+        .to_move(&engine.board);
+    assert_eq!(
+        engine.notation.format(&engine.board, &m, &engine.iccs),
+        "h2e2"
+    );
+    engine.execute("setoption notation chinese").unwrap();
+    assert_eq!(engine.notation, Notation::Chinese);
+    assert_eq!(
+        engine.notation.format(&engine.board, &m, &engine.iccs),
+        "炮二平五"
+    );
+    engine.execute("setoption notation wxf").unwrap();
+    assert_eq!(
+        engine.notation.format(&engine.board, &m, &engine.iccs),
+        "C2=5"
+    );
+    assert!(engine.execute("setoption notation pgn").is_err());
+    engine.execute("setoption usebook false").unwrap();
+    engine.go(3);
+}
+
+#[test]
+fn test_iccs_options() {
+    let mut engine = UCCIEngine::new(None).unwrap();
+    engine
+        .execute("position startpos moves H2-E2 h9g7")
+        .unwrap();
+    let fen = engine.board.to_fen();
+    engine.execute("setoption rankorigin top").unwrap();
+    engine.execute("setoption iccsstyle dash").unwrap();
+    engine
+        .execute("position startpos moves H7-E7 H0-G2")
+        .unwrap();
+    assert_eq!(engine.board.to_fen(), fen);
+    let m = CompactMove::from_iccs("b0c2").unwrap();
+    assert_eq!(engine.iccs.format(m), "B9-C7");
+    assert!(engine.execute("setoption rankorigin middle").is_err());
+}
+
+#[test]
+fn test_mirrored_book() {
+    use crate::book::BookRecord;
+
+    let mut engine = UCCIEngine::new(None).unwrap();
+    let board =
+        Board::from_fen("rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR b - - 1 1")
+            .unwrap();
+    engine.book = Book::new(vec![BookRecord {
+        key: board.hash(),
+        m: CompactMove::from_iccs("h9g7").unwrap(),
+        weight: 1,
+    }]);
+    engine.position("startpos").unwrap();
+    assert_eq!(engine.search_in_book(), None);
+    engine.position("startpos moves h2e2").unwrap();
+    assert_eq!(
+        engine.search_in_book().map(|m| m.to_iccs()),
+        Some("h9g7".to_owned())
+    );
+    // 库里只有炮二平五，炮八平五时走镜像的马2进3
+    engine.position("startpos moves b2e2").unwrap();
+    assert_eq!(engine.board.mirror_lr().hash(), board.hash());
+    assert_eq!(
+        engine.search_in_book().map(|m| m.to_iccs()),
+        Some("b9c7".to_owned())
+    );
+}
+
+#[test]
+fn test_book_mode() {
+    use crate::book::BookRecord;
+
+    let mut engine = UCCIEngine::new(None).unwrap();
+    engine.position("startpos moves h2e2").unwrap();
+    let key = engine.board.hash();
+    let entry = |key: u64, iccs: &str, weight: u16| BookRecord {
+        key,
+        m: CompactMove::from_iccs(iccs).unwrap(),
+        weight,
+    };
+    // 前后放一些别的局面，相同局面的条目要全部找到
+    engine.book = Book::new(vec![
+        entry(key.wrapping_add(1), "i9i8", 100),
+        entry(key, "h9g7", 3),
+        entry(key, "b9c7", 8),
+        entry(key, "h7e7", 0),
+        // 车被卒挡住，走不了
+        entry(key, "a9a5", 50),
+        entry(key.wrapping_sub(1), "a9a8", 100),
+    ]);
+    let mut moves = engine
+        .book_moves()
+        .into_iter()
+        .map(|(m, w)| (m.to_iccs(), w))
+        .collect::<Vec<(String, i32)>>();
+    moves.sort();
+    assert_eq!(
+        moves,
+        [
+            ("b9c7".to_owned(), 8),
+            ("h7e7".to_owned(), 0),
+            ("h9g7".to_owned(), 3)
+        ]
+    );
+
+    engine.execute("setoption bookmode best").unwrap();
+    assert_eq!(engine.book_mode, BookMode::Best);
+    assert_eq!(engine.search_in_book().unwrap().to_iccs(), "b9c7");
+    // 权重为0的着法不会被选中
+    engine.execute("setoption bookmode weighted").unwrap();
+    for _ in 0..50 {
+        assert_ne!(engine.search_in_book().unwrap().to_iccs(), "h7e7");
+    }
+    engine.execute("setoption bookmode uniform").unwrap();
+    let picked = (0..200)
+        .map(|_| engine.search_in_book().unwrap())
+        .collect::<Vec<CompactMove>>();
+    assert!(picked.contains(&CompactMove::from_iccs("h7e7").unwrap()));
+    assert!(engine.execute("setoption bookmode random").is_err());
+}
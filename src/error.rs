@@ -0,0 +1,294 @@
+use std::{fmt, io};
+
+use crate::board::{Chess, Player, Position};
+
+// 解析FEN时的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenError {
+    // 缺少棋子布局
+    Empty,
+    // 行数不是10
+    RowCount(usize),
+    // 某一行的格子数不是9，行号从0开始
+    RowLength(usize),
+    // 不认识的棋子字母
+    InvalidPiece(char),
+    // 缺少轮到哪一方走
+    MissingSide,
+    // 不是w、r或b
+    InvalidSide(String),
+    // 回合数不是数字
+    InvalidCounter(String),
+    // 格式正确，但局面不可能出现，棋谱里的FEN要检查
+    InvalidPosition(Vec<PositionError>),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::Empty => write!(f, "FEN为空"),
+            FenError::RowCount(n) => write!(f, "FEN应有10行，实际有{}行", n),
+            FenError::RowLength(row) => write!(f, "FEN第{}行不是9列", row + 1),
+            FenError::InvalidPiece(c) => write!(f, "FEN中有无效的棋子{}", c),
+            FenError::MissingSide => write!(f, "FEN缺少走子方"),
+            FenError::InvalidSide(s) => write!(f, "FEN中有无效的走子方{}", s),
+            FenError::InvalidCounter(s) => write!(f, "FEN中有无效的回合数{}", s),
+            FenError::InvalidPosition(errors) => write_position_errors(f, errors),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+// 解析着法或坐标时的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveParseError {
+    // 长度不对
+    InvalidLength(String),
+    // 超出棋盘的坐标
+    InvalidSquare(String),
+    // 格式正确，但当前局面没有这样的着法
+    NoSuchMove(String),
+}
+
+impl fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveParseError::InvalidLength(s) => write!(f, "着法{}的长度不对", s),
+            MoveParseError::InvalidSquare(s) => write!(f, "着法{}中有无效的坐标", s),
+            MoveParseError::NoSuchMove(s) => write!(f, "当前局面没有着法{}", s),
+        }
+    }
+}
+
+impl std::error::Error for MoveParseError {}
+
+// 加载开局库时的错误，行号从1开始
+#[derive(Debug)]
+pub enum BookError {
+    Io(io::Error),
+    // 缺少着法、权重或FEN
+    MissingField(usize),
+    InvalidWeight(usize, String),
+    Move(usize, MoveParseError),
+    Fen(usize, FenError),
+    // 二进制开局库的文件头或长度不对
+    InvalidFormat,
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BookError::Io(e) => write!(f, "无法读取开局库：{}", e),
+            BookError::MissingField(line) => write!(f, "开局库第{}行缺少字段", line),
+            BookError::InvalidWeight(line, s) => {
+                write!(f, "开局库第{}行的权重{}无效", line, s)
+            }
+            BookError::Move(line, e) => write!(f, "开局库第{}行：{}", line, e),
+            BookError::Fen(line, e) => write!(f, "开局库第{}行：{}", line, e),
+            BookError::InvalidFormat => write!(f, "二进制开局库格式不对"),
+        }
+    }
+}
+
+impl std::error::Error for BookError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BookError::Io(e) => Some(e),
+            BookError::Move(_, e) => Some(e),
+            BookError::Fen(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BookError {
+    fn from(e: io::Error) -> Self {
+        BookError::Io(e)
+    }
+}
+
+// 读取PGN棋谱时的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnError {
+    // 无法解析的标签
+    InvalidTag(String),
+    Fen(FenError),
+    Move(MoveParseError),
+    // 变着的括号不匹配
+    UnbalancedVariation,
+    // 注释没有结束的大括号
+    UnterminatedComment,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::InvalidTag(s) => write!(f, "无效的PGN标签{}", s),
+            PgnError::Fen(e) => e.fmt(f),
+            PgnError::Move(e) => e.fmt(f),
+            PgnError::UnbalancedVariation => write!(f, "变着的括号不匹配"),
+            PgnError::UnterminatedComment => write!(f, "注释没有结束"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl From<FenError> for PgnError {
+    fn from(e: FenError) -> Self {
+        PgnError::Fen(e)
+    }
+}
+
+impl From<MoveParseError> for PgnError {
+    fn from(e: MoveParseError) -> Self {
+        PgnError::Move(e)
+    }
+}
+
+// 读取XQF棋谱时的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum XqfError {
+    // 文件头不是XQ
+    InvalidSignature,
+    // 文件比记录的内容短
+    Truncated,
+    // 局面中不能走的着法，XQF格式的(起点, 终点)
+    IllegalMove(u8, u8),
+    // 文件头里的棋子位置不可能出现
+    InvalidPosition(Vec<PositionError>),
+}
+
+impl fmt::Display for XqfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XqfError::InvalidSignature => write!(f, "不是XQF棋谱"),
+            XqfError::Truncated => write!(f, "XQF棋谱不完整"),
+            XqfError::IllegalMove(from, to) => {
+                write!(f, "XQF棋谱中有不合法的着法{:02}-{:02}", from, to)
+            }
+            XqfError::InvalidPosition(errors) => write_position_errors(f, errors),
+        }
+    }
+}
+
+impl std::error::Error for XqfError {}
+
+// 读取DhtmlXQ棋谱时的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum DhtmlXqError {
+    // 着法不是四个数字一组
+    InvalidMoveList(String),
+    // 局面中不能走的着法
+    IllegalMove(String),
+    // binit里的棋子位置不可能出现
+    InvalidPosition(Vec<PositionError>),
+}
+
+impl fmt::Display for DhtmlXqError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DhtmlXqError::InvalidMoveList(s) => write!(f, "无效的DhtmlXQ着法{}", s),
+            DhtmlXqError::IllegalMove(s) => write!(f, "DhtmlXQ棋谱中有不合法的着法{}", s),
+            DhtmlXqError::InvalidPosition(errors) => write_position_errors(f, errors),
+        }
+    }
+}
+
+impl std::error::Error for DhtmlXqError {}
+
+// 局面中不可能出现的情况
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionError {
+    MissingKing(Player),
+    // 棋子个数超过了上限
+    TooMany(Chess, usize),
+    // 棋子在走不到的位置上
+    IllegalSquare(Chess, Position),
+    FacingKings,
+    // 不该走棋的一方正被将军
+    OpponentInCheck(Player),
+}
+
+fn chess_name(chess: &Chess) -> char {
+    chess.fen_char().unwrap_or('-')
+}
+
+fn player_name(player: &Player) -> &'static str {
+    match player {
+        Player::Red => "红方",
+        Player::Black => "黑方",
+    }
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::MissingKing(player) => write!(f, "{}没有将帅", player_name(player)),
+            PositionError::TooMany(chess, count) => {
+                write!(f, "棋子{}有{}个", chess_name(chess), count)
+            }
+            PositionError::IllegalSquare(chess, pos) => {
+                write!(f, "棋子{}不能在{}", chess_name(chess), pos)
+            }
+            PositionError::FacingKings => write!(f, "将帅照面"),
+            PositionError::OpponentInCheck(player) => {
+                write!(f, "{}不该走棋却被将军", player_name(player))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+fn write_position_errors(f: &mut fmt::Formatter, errors: &[PositionError]) -> fmt::Result {
+    write!(f, "局面不合法：")?;
+    for (i, e) in errors.iter().enumerate() {
+        if i > 0 {
+            write!(f, "，")?;
+        }
+        write!(f, "{}", e)?;
+    }
+    Ok(())
+}
+
+// UCCI指令的错误，报告给界面后继续等待下一条指令
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    Fen(FenError),
+    Move(MoveParseError),
+    InvalidPosition(Vec<PositionError>),
+    // 不合法的着法，序号从1开始
+    IllegalMove(usize, String),
+    // 指令的参数缺失或无法解析
+    InvalidArgument(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Fen(e) => e.fmt(f),
+            CommandError::Move(e) => e.fmt(f),
+            CommandError::InvalidPosition(errors) => write_position_errors(f, errors),
+            CommandError::IllegalMove(index, m) => write!(f, "第{}步{}不合法", index, m),
+            CommandError::InvalidArgument(s) => write!(f, "无效的参数：{}", s),
+            CommandError::Unsupported(s) => write!(f, "not support: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<FenError> for CommandError {
+    fn from(e: FenError) -> Self {
+        CommandError::Fen(e)
+    }
+}
+
+impl From<MoveParseError> for CommandError {
+    fn from(e: MoveParseError) -> Self {
+        CommandError::Move(e)
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    board::{Board, Move},
+    error::FenError,
+    pgn::{GameResult, PgnGame, PgnLine, PgnMove},
+    status::GameStatus,
+};
+
+pub type NodeId = usize;
+
+// 着法树的一个节点，根节点没有着法
+#[derive(Clone, PartialEq, Debug)]
+pub struct Node {
+    pub m: Option<Move>,
+    pub parent: Option<NodeId>,
+    // 第一个是主线，其余的是变着
+    pub children: Vec<NodeId>,
+    // 这一步之前的注释，一般用在变着的开头
+    pub starting_comment: Option<String>,
+    // 这一步之后的注释，根节点的是整局棋开头的注释
+    pub comment: Option<String>,
+    pub nags: Vec<u8>,
+}
+
+impl Node {
+    fn new(m: Option<Move>, parent: Option<NodeId>) -> Self {
+        Node {
+            m,
+            parent,
+            children: vec![],
+            starting_comment: None,
+            comment: None,
+            nags: vec![],
+        }
+    }
+}
+
+// 一局棋的记录：初始局面、带变着的着法树、注释和对局信息，各种棋谱格式都转换成它
+// 和搜索用的Board::move_history分开，另外维护一个当前节点上的局面用来浏览
+pub struct Game {
+    pub headers: Vec<(String, String)>,
+    pub result: GameResult,
+    initial_fen: String,
+    // 节点只增加不删除，下标就是NodeId，0是根节点
+    nodes: Vec<Node>,
+    current: NodeId,
+    board: Board,
+}
+
+impl Game {
+    pub fn new(board: Board) -> Self {
+        Game {
+            headers: vec![],
+            result: GameResult::Unknown,
+            initial_fen: board.to_fen(),
+            nodes: vec![Node::new(None, None)],
+            current: 0,
+            board,
+        }
+    }
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_owned(),
+            None => self.headers.push((name.to_owned(), value.to_owned())),
+        }
+    }
+    pub fn root(&self) -> NodeId {
+        0
+    }
+    pub fn current(&self) -> NodeId {
+        self.current
+    }
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id]
+    }
+    // 当前节点上的局面
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+    // 当前节点上的对局状态，重复局面按从开局到这里的路线判断
+    pub fn status(&mut self) -> GameStatus {
+        self.board.game_status()
+    }
+    pub fn initial_board(&self) -> Board {
+        Board::from_fen(&self.initial_fen).unwrap()
+    }
+    // 在当前节点后面走一步，已经有这一步时直接走过去，否则加成最后一个变着
+    // 不合法的着法返回None
+    pub fn add_move(&mut self, m: Move) -> Option<NodeId> {
+        let existing = self.nodes[self.current]
+            .children
+            .iter()
+            .copied()
+            .find(|&id| self.nodes[id].m == Some(m));
+        let id = match existing {
+            Some(id) => id,
+            None => {
+                if !self.board.is_legal(&m) {
+                    return None;
+                }
+                let id = self.nodes.len();
+                self.nodes.push(Node::new(Some(m), Some(self.current)));
+                self.nodes[self.current].children.push(id);
+                id
+            }
+        };
+        self.board.apply_move(&m);
+        self.current = id;
+        Some(id)
+    }
+    // 沿主线前进一步
+    pub fn forward(&mut self) -> bool {
+        match self.nodes[self.current].children.first() {
+            Some(&id) => {
+                self.board.apply_move(&self.nodes[id].m.unwrap());
+                self.current = id;
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn back(&mut self) -> bool {
+        match self.nodes[self.current].parent {
+            Some(parent) => {
+                self.board.unapply_move(&self.nodes[self.current].m.unwrap());
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+    // 从根节点到id经过的节点，不包括根节点
+    pub fn path(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path = vec![];
+        let mut node = id;
+        while let Some(parent) = self.nodes[node].parent {
+            path.push(node);
+            node = parent;
+        }
+        path.reverse();
+        path
+    }
+    // 跳到任意一个节点，先退到公共的祖先再走过去
+    pub fn goto(&mut self, id: NodeId) {
+        let path = self.path(id);
+        while self.current != 0 && !path.contains(&self.current) {
+            self.back();
+        }
+        let start = path
+            .iter()
+            .position(|&node| node == self.current)
+            .map_or(0, |i| i + 1);
+        for &node in &path[start..] {
+            self.board.apply_move(&self.nodes[node].m.unwrap());
+        }
+        self.current = id;
+    }
+    // 把一个变着提前一位，已经是主线时返回false
+    pub fn promote_variation(&mut self, id: NodeId) -> bool {
+        let parent = match self.nodes[id].parent {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let children = &mut self.nodes[parent].children;
+        let index = children.iter().position(|&child| child == id).unwrap();
+        if index == 0 {
+            return false;
+        }
+        children.swap(index, index - 1);
+        true
+    }
+    // 把一个变着直接变成主线
+    pub fn promote_to_main(&mut self, id: NodeId) {
+        let mut node = id;
+        while let Some(parent) = self.nodes[node].parent {
+            let children = &mut self.nodes[parent].children;
+            let index = children.iter().position(|&child| child == node).unwrap();
+            children[..=index].rotate_right(1);
+            node = parent;
+        }
+    }
+    // 主线上的所有着法
+    pub fn mainline(&self) -> Vec<Move> {
+        let mut moves = vec![];
+        let mut node = 0;
+        while let Some(&child) = self.nodes[node].children.first() {
+            moves.push(self.nodes[child].m.unwrap());
+            node = child;
+        }
+        moves
+    }
+    // 把一串着法接在parent后面，变着接成它们第一步的兄弟节点
+    fn add_line(&mut self, parent: NodeId, line: &PgnLine) {
+        let mut parent = parent;
+        for (i, pm) in line.moves.iter().enumerate() {
+            let id = self.nodes.len();
+            let mut node = Node::new(Some(pm.m), Some(parent));
+            node.comment = pm.comment.clone();
+            node.nags = pm.nags.clone();
+            if i == 0 {
+                node.starting_comment = line.comment.clone();
+            }
+            self.nodes.push(node);
+            self.nodes[parent].children.push(id);
+            for variation in &pm.variations {
+                self.add_line(parent, variation);
+            }
+            parent = id;
+        }
+    }
+    // 从parent的第index个子节点开始，沿主线得到一串着法
+    fn line(&self, parent: NodeId, index: usize) -> PgnLine {
+        let mut line = PgnLine::default();
+        let (mut parent, mut index) = (parent, index);
+        while let Some(&id) = self.nodes[parent].children.get(index) {
+            let node = &self.nodes[id];
+            if line.moves.is_empty() {
+                line.comment = node.starting_comment.clone();
+            }
+            // 变着挂在同一局面的主线着法上
+            let variations = if index == 0 {
+                (1..self.nodes[parent].children.len())
+                    .map(|i| self.line(parent, i))
+                    .collect()
+            } else {
+                vec![]
+            };
+            line.moves.push(PgnMove {
+                m: node.m.unwrap(),
+                nags: node.nags.clone(),
+                comment: node.comment.clone(),
+                variations,
+            });
+            parent = id;
+            index = 0;
+        }
+        line
+    }
+    // 各种棋谱格式读进来的PgnGame都可以转成Game，浏览位置在开局
+    pub fn from_pgn_game(pgn: &PgnGame) -> Result<Self, FenError> {
+        let mut game = Game::new(pgn.start_board()?);
+        game.headers = pgn.tags.clone();
+        game.result = pgn.result;
+        game.nodes[0].comment = pgn.mainline.comment.clone();
+        let mainline = PgnLine {
+            comment: None,
+            moves: pgn.mainline.moves.clone(),
+        };
+        game.add_line(0, &mainline);
+        Ok(game)
+    }
+    pub fn to_pgn_game(&self) -> PgnGame {
+        let mut pgn = PgnGame {
+            tags: self.headers.clone(),
+            mainline: self.line(0, 0),
+            result: self.result,
+        };
+        pgn.mainline.comment = self.nodes[0].comment.clone();
+        if self.initial_fen != Board::init().to_fen() {
+            pgn.set_tag("FEN", &self.initial_fen);
+        }
+        pgn
+    }
+}
+
+#[test]
+fn test_game_navigation() {
+    use crate::compact::CompactMove;
+
+    let mut game = Game::new(Board::init());
+    let play = |game: &mut Game, iccs: &str| {
+        let m = CompactMove::from_iccs(iccs).unwrap().to_move(game.board());
+        game.add_move(m)
+    };
+    let e2 = play(&mut game, "h2e2").unwrap();
+    let g7 = play(&mut game, "h9g7").unwrap();
+    game.back();
+    let c7 = play(&mut game, "b9c7").unwrap();
+    assert_eq!(game.node(e2).children, [g7, c7]);
+    // 已经有的着法不会重复添加
+    game.back();
+    assert_eq!(play(&mut game, "h9g7"), Some(g7));
+    // 不合法的着法
+    assert_eq!(play(&mut game, "h9g7"), None);
+    assert_eq!(game.current(), g7);
+
+    let fen_g7 = game.board().to_fen();
+    game.goto(c7);
+    assert_eq!(game.path(c7), [e2, c7]);
+    let fen_c7 = game.board().to_fen();
+    assert_ne!(fen_g7, fen_c7);
+    game.goto(game.root());
+    assert_eq!(game.board().to_fen(), Board::init().to_fen());
+    assert!(!game.back());
+    assert!(game.forward());
+    assert!(game.forward());
+    assert_eq!(game.current(), g7);
+    assert_eq!(game.board().to_fen(), fen_g7);
+
+    assert!(game.promote_variation(c7));
+    assert!(!game.promote_variation(c7));
+    assert_eq!(game.node(e2).children, [c7, g7]);
+    game.goto(game.root());
+    game.forward();
+    game.forward();
+    assert_eq!(game.current(), c7);
+    assert_eq!(game.board().to_fen(), fen_c7);
+
+    game.promote_to_main(g7);
+    assert_eq!(game.node(e2).children, [g7, c7]);
+    assert_eq!(game.mainline().len(), 2);
+}
+
+#[test]
+fn test_game_pgn() {
+    let text = "[Event \"测试\"]\n\n{开局} 1. H2-E2 {中炮} H9-G7 2. H0-G2 \
+                (2. B0-C2 {另一种} B9-C7 $2) I9-H9 $1 1-0\n";
+    let pgn = PgnGame::parse(text).unwrap();
+    let mut game = Game::from_pgn_game(&pgn).unwrap();
+    assert_eq!(game.header("Event"), Some("测试"));
+    assert_eq!(game.result, GameResult::RedWin);
+    assert_eq!(game.node(game.root()).comment.as_deref(), Some("开局"));
+    assert_eq!(game.mainline(), pgn.moves());
+    assert_eq!(game.to_pgn_game(), pgn);
+
+    // 走到变着里，加一步新的着法，再写回PGN
+    game.forward();
+    game.forward();
+    let variation = game.node(game.current()).children[1];
+    game.goto(variation);
+    game.forward();
+    let nags = &game.node(game.current()).nags;
+    assert_eq!(nags, &[2]);
+    game.node_mut(variation).starting_comment = Some("变着".to_owned());
+    let pgn = PgnGame::parse(&game.to_pgn_game().to_pgn().unwrap()).unwrap();
+    let line = &pgn.mainline.moves[2].variations[0];
+    assert_eq!(line.comment.as_deref(), Some("变着"));
+    assert_eq!(line.moves[0].comment.as_deref(), Some("另一种"));
+
+    // 残局的初始局面写到FEN标签里
+    let board = Board::from_fen("3k5/9/9/9/9/9/9/9/9/4KR3 w - - 0 1").unwrap();
+    let game = Game::new(board);
+    assert_eq!(
+        game.to_pgn_game().tag("FEN"),
+        Some("3k5/9/9/9/9/9/9/9/9/4KR3 w - - 0 1")
+    );
+}
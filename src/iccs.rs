@@ -0,0 +1,140 @@
+use crate::{
+    board::{Position, BOARD_HEIGHT, BOARD_WIDTH},
+    compact::CompactMove,
+    error::MoveParseError,
+};
+
+// 坐标的行号从哪一边数起，列号总是从红方左手边的a开始
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RankOrigin {
+    // ICCS标准，红方底线是0
+    Bottom,
+    // 黑方底线是0
+    Top,
+}
+
+// ICCS坐标记谱的写法，解析时不区分大小写，也接受中间的横线
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct IccsFormat {
+    pub origin: RankOrigin,
+    pub upper_case: bool,
+    // 起点和终点之间加横线，例如H2-E2
+    pub dash: bool,
+}
+
+impl Default for IccsFormat {
+    fn default() -> Self {
+        IccsFormat {
+            origin: RankOrigin::Bottom,
+            upper_case: false,
+            dash: false,
+        }
+    }
+}
+
+impl IccsFormat {
+    // 界面常用的H2-E2写法
+    pub fn dashed() -> Self {
+        IccsFormat {
+            origin: RankOrigin::Bottom,
+            upper_case: true,
+            dash: true,
+        }
+    }
+    fn row(&self, rank: i32) -> i32 {
+        match self.origin {
+            RankOrigin::Bottom => BOARD_HEIGHT - 1 - rank,
+            RankOrigin::Top => rank,
+        }
+    }
+    pub fn parse_position(&self, s: &str) -> Result<Position, MoveParseError> {
+        match s.as_bytes() {
+            [file, rank @ b'0'..=b'9'] => {
+                let file = file.to_ascii_lowercase();
+                if (b'a'..b'a' + BOARD_WIDTH as u8).contains(&file) {
+                    Ok(Position::new(
+                        self.row((rank - b'0') as i32),
+                        (file - b'a') as i32,
+                    ))
+                } else {
+                    Err(MoveParseError::InvalidSquare(s.to_owned()))
+                }
+            }
+            [_, _] => Err(MoveParseError::InvalidSquare(s.to_owned())),
+            _ => Err(MoveParseError::InvalidLength(s.to_owned())),
+        }
+    }
+    // 接受h2e2、H2E2、h2-e2、H2-E2等写法
+    pub fn parse(&self, s: &str) -> Result<CompactMove, MoveParseError> {
+        let trimmed = s.trim();
+        let compact = match trimmed.len() {
+            4 => trimmed.to_owned(),
+            5 if trimmed.as_bytes()[2] == b'-' => trimmed.replacen('-', "", 1),
+            _ => return Err(MoveParseError::InvalidLength(s.to_owned())),
+        };
+        if !compact.is_char_boundary(2) {
+            return Err(MoveParseError::InvalidSquare(s.to_owned()));
+        }
+        let square = |sq: &str| {
+            self.parse_position(sq)
+                .map_err(|_| MoveParseError::InvalidSquare(s.to_owned()))
+        };
+        Ok(CompactMove::new(
+            square(&compact[..2])?,
+            square(&compact[2..])?,
+        ))
+    }
+    pub fn format_position(&self, pos: Position) -> String {
+        let file = (pos.col as u8 + b'a') as char;
+        let rank = self.row(pos.row);
+        if self.upper_case {
+            format!("{}{}", file.to_ascii_uppercase(), rank)
+        } else {
+            format!("{}{}", file, rank)
+        }
+    }
+    pub fn format(&self, m: CompactMove) -> String {
+        let separator = if self.dash { "-" } else { "" };
+        format!(
+            "{}{}{}",
+            self.format_position(m.source()),
+            separator,
+            self.format_position(m.target())
+        )
+    }
+}
+
+#[test]
+fn test_iccs_format() {
+    let m = CompactMove::from_iccs("h2e2").unwrap();
+    let standard = IccsFormat::default();
+    for s in ["h2e2", "H2E2", "h2-e2", "H2-E2", " h2e2\n"] {
+        assert_eq!(standard.parse(s), Ok(m));
+    }
+    assert_eq!(standard.format(m), "h2e2");
+    assert_eq!(IccsFormat::dashed().format(m), "H2-E2");
+    assert_eq!(IccsFormat::dashed().parse("h2e2"), Ok(m));
+
+    // 黑方底线为0时，红方的炮在第7行
+    let top = IccsFormat {
+        origin: RankOrigin::Top,
+        ..IccsFormat::default()
+    };
+    assert_eq!(top.parse("h7e7"), Ok(m));
+    assert_eq!(top.format(m), "h7e7");
+    assert_eq!(top.parse_position("a0"), Ok(Position::new(0, 0)));
+    assert_eq!(standard.parse_position("a0"), Ok(Position::new(9, 0)));
+
+    for s in ["h2", "h2e2e", "h2+e2", "h2--e2"] {
+        assert_eq!(
+            standard.parse(s),
+            Err(MoveParseError::InvalidLength(s.to_owned()))
+        );
+    }
+    for s in ["j2e2", "h2-ex", "炮2"] {
+        assert_eq!(
+            standard.parse(s),
+            Err(MoveParseError::InvalidSquare(s.to_owned()))
+        );
+    }
+}
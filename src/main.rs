@@ -1,13 +1,68 @@
 #![allow(dead_code)]
 
+use std::path::PathBuf;
+
 use engine::UCCIEngine;
 
 mod constant;
+mod bitboard;
 mod board;
+mod book;
+mod chinese;
+mod compact;
+mod dhtmlxq;
 mod engine;
+mod error;
+mod game;
+mod iccs;
+mod movegen;
+mod notation;
+mod perft;
+mod pgn;
+mod picker;
+mod render;
+mod status;
+mod svg;
+mod symmetry;
+mod validate;
+mod wxf;
+mod xqf;
 mod zobrist;
 #[macro_use]
 extern crate lazy_static;
+// 默认的开局库是程序所在目录下用convertbook生成的BOOK.BIN
+fn default_book_path() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("BOOK.BIN")))
+        .unwrap_or_else(|| PathBuf::from("BOOK.BIN"))
+}
+
 fn main() {
-    UCCIEngine::new(Some("/home/nealian/desktop_new/chess/chess_ai/BOOK.DAT")).start();
+    // chess_ai convertbook <文本开局库> <二进制开局库>
+    // chess_ai [开局库]
+    let args = std::env::args().collect::<Vec<String>>();
+    if let [_, cmd, text, binary] = args.as_slice() {
+        if cmd == "convertbook" {
+            match book::convert(text, binary) {
+                Ok(count) => println!("转换完成，共{}条记录", count),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+    }
+    let book_path = match args.as_slice() {
+        [_, path] => PathBuf::from(path),
+        _ => default_book_path(),
+    };
+    let engine = UCCIEngine::new(Some(&book_path));
+    let mut engine = engine.unwrap_or_else(|e| {
+        // 开局库加载失败时不用开局库
+        eprintln!("{}", e);
+        UCCIEngine::new(None).unwrap()
+    });
+    engine.start();
 }
@@ -0,0 +1,620 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    bitboard::{position, square, BitBoard},
+    board::{
+        in_board, in_country, in_palace, Board, Chess, ChessType, Move, Player, Position,
+        BOARD_HEIGHT, BOARD_WIDTH,
+    },
+    compact::CompactMove,
+};
+
+const SQUARE_COUNT: usize = (BOARD_WIDTH * BOARD_HEIGHT) as usize;
+
+// 合法局面(Board::validate通过)的伪合法着法不会超过这个数，
+// 棋子多出来的局面可能超过，多出来的着法会被丢掉
+pub const MAX_MOVES: usize = 128;
+
+// 定长的着法列表，直接分配在栈上，避免走法生成时反复申请内存。
+// 只保存16位的CompactMove，列表只有256字节，用到时再结合局面还原成Move
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [CompactMove; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        MoveList {
+            moves: [CompactMove(0); MAX_MOVES],
+            len: 0,
+        }
+    }
+    pub fn push(&mut self, m: CompactMove) {
+        debug_assert!(self.len < MAX_MOVES, "MoveList溢出，局面没有经过validate");
+        if self.len < MAX_MOVES {
+            self.moves[self.len] = m;
+            self.len += 1;
+        }
+    }
+    pub fn insert(&mut self, index: usize, m: CompactMove) {
+        debug_assert!(self.len < MAX_MOVES, "MoveList溢出，局面没有经过validate");
+        if self.len < MAX_MOVES {
+            self.moves.copy_within(index..self.len, index + 1);
+            self.moves[index] = m;
+            self.len += 1;
+        }
+    }
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [CompactMove];
+    fn deref(&self) -> &[CompactMove] {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [CompactMove] {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+pub struct IntoIter {
+    list: MoveList,
+    index: usize,
+}
+
+impl Iterator for IntoIter {
+    type Item = CompactMove;
+    fn next(&mut self) -> Option<CompactMove> {
+        if self.index < self.list.len {
+            self.index += 1;
+            Some(self.list.moves[self.index - 1])
+        } else {
+            None
+        }
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = CompactMove;
+    type IntoIter = IntoIter;
+    fn into_iter(self) -> IntoIter {
+        IntoIter {
+            list: self,
+            index: 0,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a CompactMove;
+    type IntoIter = std::slice::Iter<'a, CompactMove>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// 生成哪些着法
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GenType {
+    All,
+    Captures,
+    Quiets,
+}
+
+impl GenType {
+    // 落点上是target时是否生成这步着法（己方棋子已经排除）
+    pub fn accepts(&self, target: Chess) -> bool {
+        match self {
+            GenType::All => true,
+            GenType::Captures => target != Chess::None,
+            GenType::Quiets => target == Chess::None,
+        }
+    }
+}
+
+// 一步走法：落点，以及要求为空的蹩脚位置（马腿、象眼）
+#[derive(Clone, Copy, Debug)]
+pub struct Step {
+    pub to: Position,
+    pub block: Option<Position>,
+}
+
+// 某个格子上所有可能的走法，最多8个（马）
+#[derive(Clone, Copy)]
+pub struct Steps {
+    steps: [Step; 8],
+    len: usize,
+}
+
+impl Steps {
+    fn new() -> Self {
+        Steps {
+            steps: [Step {
+                to: Position::new(0, 0),
+                block: None,
+            }; 8],
+            len: 0,
+        }
+    }
+    fn push(&mut self, to: Position, block: Option<Position>) {
+        self.steps[self.len] = Step { to, block };
+        self.len += 1;
+    }
+}
+
+impl Deref for Steps {
+    type Target = [Step];
+    fn deref(&self) -> &[Step] {
+        &self.steps[..self.len]
+    }
+}
+
+// 预先计算好的帅、士、相、马、兵的落点表，[玩家][格子]
+pub struct StepTables {
+    pub king: [[Steps; SQUARE_COUNT]; 2],
+    pub advisor: [[Steps; SQUARE_COUNT]; 2],
+    pub bishop: [[Steps; SQUARE_COUNT]; 2],
+    pub knight: [Steps; SQUARE_COUNT],
+    pub pawn: [[Steps; SQUARE_COUNT]; 2],
+    // 从将帅出发反查能将军的马，蹩脚位置是马腿
+    pub knight_check: [Steps; SQUARE_COUNT],
+    // 从将帅出发反查能将军的兵，[兵的玩家][格子]
+    pub pawn_check: [[Steps; SQUARE_COUNT]; 2],
+}
+
+impl StepTables {
+    fn new() -> Box<Self> {
+        let mut tables = Box::new(StepTables {
+            king: [[Steps::new(); SQUARE_COUNT]; 2],
+            advisor: [[Steps::new(); SQUARE_COUNT]; 2],
+            bishop: [[Steps::new(); SQUARE_COUNT]; 2],
+            knight: [Steps::new(); SQUARE_COUNT],
+            pawn: [[Steps::new(); SQUARE_COUNT]; 2],
+            knight_check: [Steps::new(); SQUARE_COUNT],
+            pawn_check: [[Steps::new(); SQUARE_COUNT]; 2],
+        });
+        for sq in 0..SQUARE_COUNT {
+            let pos = position(sq);
+            for (leg, targets) in [
+                (pos.up(1), [pos.up(2).left(1), pos.up(2).right(1)]),
+                (pos.down(1), [pos.down(2).left(1), pos.down(2).right(1)]),
+                (pos.left(1), [pos.up(1).left(2), pos.down(1).left(2)]),
+                (pos.right(1), [pos.up(1).right(2), pos.down(1).right(2)]),
+            ] {
+                for to in targets {
+                    if in_board(to) {
+                        tables.knight[sq].push(to, Some(leg));
+                    }
+                }
+            }
+            for (leg, knights) in [
+                (pos.up(1).left(1), [pos.up(2).left(1), pos.up(1).left(2)]),
+                (pos.up(1).right(1), [pos.up(2).right(1), pos.up(1).right(2)]),
+                (
+                    pos.down(1).left(1),
+                    [pos.down(2).left(1), pos.down(1).left(2)],
+                ),
+                (
+                    pos.down(1).right(1),
+                    [pos.down(2).right(1), pos.down(1).right(2)],
+                ),
+            ] {
+                for knight in knights {
+                    if in_board(knight) {
+                        tables.knight_check[sq].push(knight, Some(leg));
+                    }
+                }
+            }
+            for player in [Player::Red, Player::Black] {
+                let p = player.value() as usize;
+                for to in [pos.up(1), pos.down(1), pos.left(1), pos.right(1)] {
+                    if in_palace(to, player) {
+                        tables.king[p][sq].push(to, None);
+                    }
+                }
+                for to in [
+                    pos.up(1).left(1),
+                    pos.up(1).right(1),
+                    pos.down(1).left(1),
+                    pos.down(1).right(1),
+                ] {
+                    if in_palace(to, player) {
+                        tables.advisor[p][sq].push(to, None);
+                    }
+                }
+                for (eye, to) in [
+                    (pos.up(1).left(1), pos.up(2).left(2)),
+                    (pos.up(1).right(1), pos.up(2).right(2)),
+                    (pos.down(1).left(1), pos.down(2).left(2)),
+                    (pos.down(1).right(1), pos.down(2).right(2)),
+                ] {
+                    // 象不能过河
+                    if in_board(to) && in_country(to.row, player) {
+                        tables.bishop[p][sq].push(to, Some(eye));
+                    }
+                }
+                let forward = if player == Player::Black {
+                    pos.down(1)
+                } else {
+                    pos.up(1)
+                };
+                let mut targets = vec![forward];
+                // 过河兵可以左右走
+                if !in_country(pos.row, player) {
+                    targets.push(pos.left(1));
+                    targets.push(pos.right(1));
+                }
+                for to in targets {
+                    if in_board(to) {
+                        tables.pawn[p][sq].push(to, None);
+                    }
+                }
+            }
+        }
+        for p in 0..2 {
+            for sq in 0..SQUARE_COUNT {
+                for step in tables.pawn[p][sq].iter() {
+                    tables.pawn_check[p][square(step.to)].push(position(sq), None);
+                }
+            }
+        }
+        tables
+    }
+}
+
+lazy_static! {
+    pub static ref STEP_TABLES: Box<StepTables> = StepTables::new();
+}
+
+// 将帅周围的牵制信息，用来判断哪些着法需要走一步验证
+struct PinInfo {
+    // 走开以后可能被将军的棋子：被车、将帅牵制，充当炮架，或者蹩着对方的马腿
+    pinned: BitBoard,
+    // 走到这些空格上会给对方的炮架上炮架
+    screens: BitBoard,
+}
+
+// 被将军时的应将范围
+struct Evasion {
+    // 吃掉将军的棋子，或者挡在将军的路线上
+    targets: BitBoard,
+    // 走开就能解除炮将军的炮架
+    screens: BitBoard,
+}
+
+// 判断伪合法着法是否合法要用到的信息
+enum Legality {
+    Evasion(Evasion),
+    Pins(PinInfo),
+}
+
+const DIRECTIONS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+impl Board {
+    // 从将帅出发，沿一个方向找到的前三个棋子，以及中间经过的空格
+    fn line_from(
+        &self,
+        king: Position,
+        (dr, dc): (i32, i32),
+    ) -> ([Option<Position>; 3], [BitBoard; 3]) {
+        let mut pieces = [None; 3];
+        let mut empties = [BitBoard::EMPTY; 3];
+        let mut found = 0;
+        let mut pos = Position::new(king.row + dr, king.col + dc);
+        while in_board(pos) && found < 3 {
+            if self.chess_at(pos) == Chess::None {
+                empties[found].set(square(pos));
+            } else {
+                pieces[found] = Some(pos);
+                found += 1;
+            }
+            pos = Position::new(pos.row + dr, pos.col + dc);
+        }
+        (pieces, empties)
+    }
+    fn pin_info(&self, king: Position) -> PinInfo {
+        let me = self.turn;
+        let enemy = me.next();
+        let mut info = PinInfo {
+            pinned: BitBoard::EMPTY,
+            screens: BitBoard::EMPTY,
+        };
+        for direction in DIRECTIONS {
+            let (pieces, empties) = self.line_from(king, direction);
+            let chess = pieces.map(|p| p.map_or(Chess::None, |p| self.chess_at(p)));
+            // 对方的炮前面没有炮架，走到中间会给它做炮架
+            if chess[0] == Chess::of(enemy, ChessType::Cannon) {
+                info.screens |= empties[0];
+            }
+            if chess[0].belong_to(me) {
+                // 车或者对面的将帅后面只隔着一个己方棋子
+                if chess[1] == Chess::of(enemy, ChessType::Rook)
+                    || chess[1] == Chess::of(enemy, ChessType::King)
+                {
+                    info.pinned.set(square(pieces[0].unwrap()));
+                }
+                // 炮隔着两个棋子，走开任何一个都会被将军
+                if chess[2] == Chess::of(enemy, ChessType::Cannon) {
+                    info.pinned.set(square(pieces[0].unwrap()));
+                    info.pinned.set(square(pieces[1].unwrap()));
+                }
+            }
+            if chess[1].belong_to(me) && chess[2] == Chess::of(enemy, ChessType::Cannon) {
+                info.pinned.set(square(pieces[1].unwrap()));
+            }
+        }
+        // 蹩着对方马腿的棋子
+        for step in STEP_TABLES.knight_check[square(king)].iter() {
+            let leg = step.block.unwrap();
+            if self.chess_at(step.to) == Chess::of(enemy, ChessType::Knight)
+                && self.chess_at(leg).belong_to(me)
+            {
+                info.pinned.set(square(leg));
+            }
+        }
+        info
+    }
+    fn evasion(&self, king: Position) -> Evasion {
+        let enemy = self.turn.next();
+        let mut evasion = Evasion {
+            targets: BitBoard::EMPTY,
+            screens: BitBoard::EMPTY,
+        };
+        for direction in DIRECTIONS {
+            let (pieces, empties) = self.line_from(king, direction);
+            let chess = pieces.map(|p| p.map_or(Chess::None, |p| self.chess_at(p)));
+            if chess[0] == Chess::of(enemy, ChessType::Rook)
+                || chess[0] == Chess::of(enemy, ChessType::King)
+            {
+                evasion.targets |= empties[0];
+                evasion.targets.set(square(pieces[0].unwrap()));
+            }
+            if chess[1] == Chess::of(enemy, ChessType::Cannon) {
+                // 吃掉炮、吃掉炮架、在炮和将帅之间再垫一个子，或者把己方的炮架走开
+                evasion.targets |= empties[0] | empties[1];
+                evasion.targets.set(square(pieces[0].unwrap()));
+                evasion.targets.set(square(pieces[1].unwrap()));
+                evasion.screens.set(square(pieces[0].unwrap()));
+            }
+        }
+        for step in STEP_TABLES.knight_check[square(king)].iter() {
+            let leg = step.block.unwrap();
+            if self.chess_at(step.to) == Chess::of(enemy, ChessType::Knight)
+                && self.chess_at(leg) == Chess::None
+            {
+                evasion.targets.set(square(step.to));
+                evasion.targets.set(square(leg));
+            }
+        }
+        for step in STEP_TABLES.pawn_check[enemy.value() as usize][square(king)].iter() {
+            if self.chess_at(step.to) == Chess::of(enemy, ChessType::Pawn) {
+                evasion.targets.set(square(step.to));
+            }
+        }
+        evasion
+    }
+    // 走一步验证这步棋走完以后己方是否被将军
+    fn is_legal_after(&mut self, m: &Move) -> bool {
+        self.apply_move(m);
+        let legal = !self.is_checked(m.player);
+        self.unapply_move(m);
+        legal
+    }
+    fn legality(&self, king: Position) -> Legality {
+        if self.is_checked(self.turn) {
+            Legality::Evasion(self.evasion(king))
+        } else {
+            Legality::Pins(self.pin_info(king))
+        }
+    }
+    // 不被将军时只验证将帅、被牵制的棋子和可能做炮架的着法，
+    // 被将军时只考虑能应将的着法
+    fn is_legal_with(&mut self, legality: &Legality, m: &Move) -> bool {
+        let king_move = m.chess.chess_type() == Some(ChessType::King);
+        match legality {
+            Legality::Evasion(evasion) => {
+                let candidate = king_move
+                    || evasion.targets.has(square(m.to))
+                    || evasion.screens.has(square(m.from));
+                candidate && self.is_legal_after(m)
+            }
+            Legality::Pins(pins) => {
+                let safe = !king_move
+                    && !pins.pinned.has(square(m.from))
+                    && !pins.screens.has(square(m.to));
+                safe || self.is_legal_after(m)
+            }
+        }
+    }
+    // 生成合法着法，只有少数着法需要走一步验证
+    pub fn generate_legal_move(&mut self, gen: GenType) -> MoveList {
+        let pseudo = self.generate_moves(gen);
+        let king = match self.king_position(self.turn) {
+            Some(king) => king,
+            None => return MoveList::new(),
+        };
+        let legality = self.legality(king);
+        let mut moves = MoveList::new();
+        for cm in pseudo {
+            if self.is_legal_with(&legality, &cm.to_move(self)) {
+                moves.push(cm);
+            }
+        }
+        moves
+    }
+    // 校验一个来自置换表或者杀手表的着法在当前局面下是否合法
+    pub fn is_legal(&mut self, m: &Move) -> bool {
+        if !m.chess.belong_to(self.turn)
+            || m.player != self.turn
+            || self.chess_at(m.from) != m.chess
+            || self.chess_at(m.to) != m.capture
+            || !in_board(m.to)
+        {
+            return false;
+        }
+        let mut targets = MoveList::new();
+        self.generate_move_for_chess(m.chess, m.from, GenType::All, &mut targets);
+        if !targets.iter().any(|t| t.target() == m.to) {
+            return false;
+        }
+        match self.king_position(self.turn) {
+            Some(king) => {
+                let legality = self.legality(king);
+                self.is_legal_with(&legality, m)
+            }
+            None => false,
+        }
+    }
+    // 当前局面的所有合法着法，可以用来校验用户的输入
+    pub fn legal_moves(&mut self) -> Vec<Move> {
+        let moves = self.generate_legal_move(GenType::All);
+        moves.iter().map(|m| m.to_move(self)).collect()
+    }
+}
+
+#[test]
+fn test_move_list() {
+    let mut list = MoveList::new();
+    assert!(list.is_empty());
+    let from = Position::new(0, 0);
+    for i in 0..10 {
+        list.push(CompactMove::new(from, Position::new(i, 0)));
+    }
+    let m = CompactMove::new(from, Position::new(0, 8));
+    list.insert(0, m);
+    assert_eq!(list.len(), 11);
+    assert_eq!(list[0].target(), Position::new(0, 8));
+    assert_eq!(list[10].target(), Position::new(9, 0));
+    assert_eq!(list.into_iter().count(), 11);
+    // 满了以后不再写入，debug下直接断言失败
+    if !cfg!(debug_assertions) {
+        let mut list = MoveList::new();
+        for _ in 0..MAX_MOVES + 2 {
+            list.push(m);
+        }
+        list.insert(0, m);
+        assert_eq!(list.len(), MAX_MOVES);
+    }
+}
+
+#[test]
+fn test_step_tables() {
+    let tables = &*STEP_TABLES;
+    // 角上的马只有两个落点
+    assert_eq!(tables.knight[0].len(), 2);
+    // 九宫中心的士有四个落点
+    let center = square(Position::new(8, 4));
+    assert_eq!(
+        tables.advisor[Player::Red.value() as usize][center].len(),
+        4
+    );
+    assert_eq!(
+        tables.advisor[Player::Black.value() as usize][center].len(),
+        0
+    );
+    // 相不能过河
+    let bishop = square(Position::new(5, 2));
+    assert_eq!(tables.bishop[Player::Red.value() as usize][bishop].len(), 2);
+}
+
+#[test]
+fn test_generate_move_speed() {
+    fn perft(board: &mut crate::board::Board, depth: i32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for m in board.generate_move(false) {
+            let m = m.to_move(board);
+            board.apply_move(&m);
+            if !board.is_checked(m.player) {
+                nodes += perft(board, depth - 1);
+            }
+            board.unapply_move(&m);
+        }
+        nodes
+    }
+    let mut board = crate::board::Board::init();
+    let start = std::time::Instant::now();
+    let nodes = perft(&mut board, 3);
+    let seconds = start.elapsed().as_secs_f64();
+    println!("{} nodes, {:.0} nodes/s", nodes, nodes as f64 / seconds);
+    assert_eq!(nodes, 79666);
+}
+
+#[test]
+fn test_legal_moves() {
+    fn perft_pseudo(board: &mut Board, depth: i32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for m in board.generate_move(false) {
+            let m = m.to_move(board);
+            board.apply_move(&m);
+            if !board.is_checked(m.player) {
+                nodes += perft_pseudo(board, depth - 1);
+            }
+            board.unapply_move(&m);
+        }
+        nodes
+    }
+    for fen in [
+        "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+        "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+        "1cbak4/9/n2a5/2p1p3p/5cp2/2n2N3/6PCP/3AB4/2C6/3A1K1N1 w - - 0 1",
+        "5a3/3k5/3aR4/9/5r3/5n3/9/3A1A1N1/5K3/9 w - - 0 1",
+        "CRN1k1b2/3ca4/4ba3/9/2nr5/9/9/4B4/4A4/4KA3 w - - 0 1",
+        "R1N1k1b2/9/3aba3/9/2nr5/2B6/9/4B4/4A4/4KA3 w - - 0 1",
+        "3k5/9/9/9/9/4c4/9/4C4/9/4K4 w - - 0 1",
+        "3k5/4a4/9/9/9/9/9/9/4A4/4K4 w - - 0 1",
+        "4k4/9/9/9/4c4/9/9/4N4/4A4/3K5 w - - 0 1",
+    ] {
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.perft(3), perft_pseudo(&mut board, 3), "{}", fen);
+        // 单独校验一个着法和生成全部合法着法的结果一致
+        let legal = board.legal_moves();
+        for m in board.generate_move(false) {
+            let m = m.to_move(&board);
+            assert_eq!(board.is_legal(&m), legal.contains(&m), "{} {:?}", fen, m);
+        }
+    }
+}
+
+#[test]
+fn test_legal_moves_bookkeeping() {
+    // 验证着法合法性时只是试走，不能改动搜索用的distance和move_history
+    let mut board = Board::init();
+    let m = board.legal_moves()[0];
+    board.do_move(&m);
+    let fen = board.to_fen();
+    for gen in [GenType::All, GenType::Captures, GenType::Quiets] {
+        board.generate_legal_move(gen);
+    }
+    let reply = board.legal_moves()[0];
+    assert!(board.is_legal(&reply));
+    assert_eq!(board.distance, 1);
+    assert_eq!(board.move_history, [m]);
+    assert_eq!(board.to_fen(), fen);
+}
@@ -0,0 +1,177 @@
+use crate::{
+    board::{Board, Chess, ChessType, Move, Player, Position, BOARD_HEIGHT, BOARD_WIDTH},
+    compact::CompactMove,
+    error::MoveParseError,
+    iccs::IccsFormat,
+};
+
+// 着法的记法
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Notation {
+    Iccs,
+    Chinese,
+    Wxf,
+}
+
+impl Notation {
+    // board是走子前的局面
+    pub fn format(self, board: &Board, m: &Move, iccs: &IccsFormat) -> String {
+        match self {
+            Notation::Iccs => iccs.format(CompactMove::from(m)),
+            Notation::Chinese => board.to_chinese(m),
+            Notation::Wxf => board.to_wxf(m),
+        }
+    }
+    // 解析后还要检查是不是当前局面的合法着法
+    pub fn parse(
+        self,
+        board: &mut Board,
+        s: &str,
+        iccs: &IccsFormat,
+    ) -> Result<Move, MoveParseError> {
+        match self {
+            Notation::Iccs => {
+                let m = iccs.parse(s)?;
+                board
+                    .legal_moves()
+                    .into_iter()
+                    .find(|l| CompactMove::from(l) == m)
+                    .ok_or_else(|| MoveParseError::NoSuchMove(s.to_owned()))
+            }
+            Notation::Chinese => board.parse_chinese(s),
+            Notation::Wxf => board.parse_wxf(s),
+        }
+    }
+}
+
+// 纵线记谱里棋子的走向
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Advance,
+    Retreat,
+    Traverse,
+}
+
+// 怎样指明走的是哪一个子
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Which {
+    // 棋子所在的纵线
+    File(i32),
+    // 同一纵线上有多个同样的子，(从前往后第几个, 共几个)
+    Order(usize, usize),
+    // 兵卒在两条纵线上都有重叠时，再加上纵线号
+    OrderFile(usize, usize, i32),
+}
+
+// 中文、WXF等纵线记谱共用的描述，纵线号都是从走棋一方看从右往左数
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Descriptive {
+    pub player: Player,
+    pub chess: ChessType,
+    pub which: Which,
+    pub direction: Direction,
+    // 平走和斜走的子是目标纵线，直走的子是步数
+    pub target: i32,
+}
+
+// 从走棋一方看，从右往左数的纵线号，1到9
+pub fn file_number(col: i32, player: Player) -> i32 {
+    match player {
+        Player::Red => BOARD_WIDTH - col,
+        Player::Black => col + 1,
+    }
+}
+
+// 离对方底线的距离，越小越靠前
+fn rank_from_front(row: i32, player: Player) -> i32 {
+    match player {
+        Player::Red => row,
+        Player::Black => BOARD_HEIGHT - 1 - row,
+    }
+}
+
+impl Board {
+    // 同一纵线上和chess一样的子，从前往后排列
+    fn tandem(&self, chess: Chess, col: i32) -> Vec<Position> {
+        let player = chess.player().unwrap();
+        let mut tandem = (0..BOARD_HEIGHT)
+            .map(|row| Position::new(row, col))
+            .filter(|&pos| self.chess_at(pos) == chess)
+            .collect::<Vec<Position>>();
+        tandem.sort_by_key(|pos| rank_from_front(pos.row, player));
+        tandem
+    }
+    // 按走子前的局面描述一步棋
+    pub fn describe(&self, m: &Move) -> Descriptive {
+        let player = m.player;
+        let chess = m.chess.chess_type().unwrap();
+        let tandem = self.tandem(m.chess, m.from.col);
+        // 同一纵线上的两个仕(相)一个只能进一个只能退，照样用纵线号
+        let by_file = matches!(chess, ChessType::Advisor | ChessType::Bishop);
+        let which = if tandem.len() < 2 || by_file {
+            Which::File(file_number(m.from.col, player))
+        } else {
+            let index = tandem.iter().position(|&pos| pos == m.from).unwrap();
+            let tandem_files = (0..BOARD_WIDTH)
+                .filter(|&col| self.tandem(m.chess, col).len() > 1)
+                .count();
+            if chess == ChessType::Pawn && tandem_files > 1 {
+                Which::OrderFile(index, tandem.len(), file_number(m.from.col, player))
+            } else {
+                Which::Order(index, tandem.len())
+            }
+        };
+        let forward = rank_from_front(m.from.row, player) - rank_from_front(m.to.row, player);
+        let direction = if forward > 0 {
+            Direction::Advance
+        } else if forward < 0 {
+            Direction::Retreat
+        } else {
+            Direction::Traverse
+        };
+        let diagonal = matches!(
+            chess,
+            ChessType::Advisor | ChessType::Bishop | ChessType::Knight
+        );
+        let target = if direction == Direction::Traverse || diagonal {
+            file_number(m.to.col, player)
+        } else {
+            forward.abs()
+        };
+        Descriptive {
+            player,
+            chess,
+            which,
+            direction,
+            target,
+        }
+    }
+}
+
+#[test]
+fn test_describe() {
+    let mut board = Board::init();
+    let m = CompactMove::from_iccs("h2e2").unwrap().to_move(&board);
+    assert_eq!(
+        board.describe(&m),
+        Descriptive {
+            player: Player::Red,
+            chess: ChessType::Cannon,
+            which: Which::File(2),
+            direction: Direction::Traverse,
+            target: 5,
+        }
+    );
+    board.apply_move(&m);
+    let m = CompactMove::from_iccs("h9g7").unwrap().to_move(&board);
+    assert_eq!(
+        board.describe(&m),
+        Descriptive {
+            player: Player::Black,
+            chess: ChessType::Knight,
+            which: Which::File(8),
+            direction: Direction::Advance,
+            target: 7,
+        }
+    );
+}
@@ -0,0 +1,123 @@
+use crate::{
+    board::{Board, Move},
+    movegen::GenType,
+};
+
+impl Board {
+    // 统计指定深度内的合法着法节点数，用来验证着法生成
+    pub fn perft(&mut self, depth: i32) -> u64 {
+        if depth <= 0 {
+            return 1;
+        }
+        let moves = self.generate_legal_move(GenType::All);
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for m in moves {
+            let m = m.to_move(self);
+            self.apply_move(&m);
+            nodes += self.perft(depth - 1);
+            self.unapply_move(&m);
+        }
+        nodes
+    }
+    // 分别统计每个着法下面的节点数，方便和其他引擎对比定位错误
+    pub fn divide(&mut self, depth: i32) -> Vec<(Move, u64)> {
+        let mut result = vec![];
+        for m in self.legal_moves() {
+            self.apply_move(&m);
+            result.push((m, self.perft(depth - 1)));
+            self.unapply_move(&m);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+fn assert_perft(fen: &str, expected: &[u64]) {
+    let mut board = Board::from_fen(fen).unwrap();
+    for (depth, nodes) in expected.iter().enumerate() {
+        assert_eq!(
+            board.perft(depth as i32 + 1),
+            *nodes,
+            "{} depth {}",
+            fen,
+            depth + 1
+        );
+    }
+    // 试走的着法都要退回去，搜索用的distance和move_history保持不变
+    assert_eq!(board.to_fen(), Board::from_fen(fen).unwrap().to_fen());
+    assert_eq!(board.distance, 0);
+    assert!(board.move_history.is_empty());
+}
+
+// 以下三个局面的数据来自公开的中国象棋perft结果
+#[test]
+fn test_perft_start() {
+    assert_perft(
+        "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+        &[44, 1920, 79666, 3290240],
+    );
+}
+
+#[test]
+fn test_perft_middle_game() {
+    assert_perft(
+        "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+        &[38, 1128, 43929, 1339047],
+    );
+}
+
+#[test]
+fn test_perft_in_check() {
+    assert_perft(
+        "1cbak4/9/n2a5/2p1p3p/5cp2/2n2N3/6PCP/3AB4/2C6/3A1K1N1 w - - 0 1",
+        &[7, 281, 8620, 326201],
+    );
+}
+
+// 以下局面的数据由格子数组和位棋盘两种实现交叉验证，作为回归测试
+#[test]
+fn test_perft_regression() {
+    assert_perft(
+        "5a3/3k5/3aR4/9/5r3/5n3/9/3A1A1N1/5K3/9 w - - 0 1",
+        &[23, 418, 8652, 183943],
+    );
+    assert_perft(
+        "CRN1k1b2/3ca4/4ba3/9/2nr5/9/9/4B4/4A4/4KA3 w - - 0 1",
+        &[28, 516, 14808, 395483],
+    );
+    assert_perft(
+        "R1N1k1b2/9/3aba3/9/2nr5/2B6/9/4B4/4A4/4KA3 w - - 0 1",
+        &[21, 364, 7626, 162837],
+    );
+}
+
+#[test]
+fn test_perft_cannon_screen() {
+    // 红炮给黑炮做了炮架：帅只能走到f0（d0会和黑将照面），炮只能横着走开
+    assert_perft("3k5/9/9/9/9/4c4/9/4C4/9/4K4 w - - 0 1", &[9, 158, 2833]);
+}
+
+#[test]
+fn test_perft_facing_kings() {
+    // 帅不能走到d0和黑将照面，仕可以随意离开中线
+    assert_perft("3k5/4a4/9/9/9/9/9/9/4A4/4K4 w - - 0 1", &[5, 25, 89]);
+}
+
+#[test]
+fn test_perft_blocked_knight() {
+    // 马的两条腿被仕和帅蹩住，只能跳到g1；仕三步，帅一步
+    assert_perft("4k4/9/9/9/9/9/9/9/4A4/3KN4 w - - 0 1", &[5, 11, 65]);
+}
+
+#[test]
+fn test_divide() {
+    let mut board = Board::init();
+    let divide = board.divide(2);
+    assert_eq!(divide.len(), 44);
+    assert_eq!(divide.iter().map(|(_, n)| n).sum::<u64>(), 1920);
+    assert_eq!(board.distance, 0);
+    assert!(board.move_history.is_empty());
+}
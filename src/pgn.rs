@@ -0,0 +1,546 @@
+use std::{fmt, iter::Peekable, vec::IntoIter};
+
+use crate::{
+    board::{Board, Move, Player},
+    error::{FenError, MoveParseError, PgnError},
+    iccs::IccsFormat,
+    notation::Notation,
+};
+
+// 每行棋谱的最大宽度
+const LINE_WIDTH: usize = 80;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum GameResult {
+    RedWin,
+    BlackWin,
+    Draw,
+    #[default]
+    Unknown,
+}
+
+impl GameResult {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "1-0" => Some(GameResult::RedWin),
+            "0-1" => Some(GameResult::BlackWin),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameResult::RedWin => write!(f, "1-0"),
+            GameResult::BlackWin => write!(f, "0-1"),
+            GameResult::Draw => write!(f, "1/2-1/2"),
+            GameResult::Unknown => write!(f, "*"),
+        }
+    }
+}
+
+// 一步棋，以及它后面的注释和替代它的变着
+#[derive(Clone, PartialEq, Debug)]
+pub struct PgnMove {
+    pub m: Move,
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    pub variations: Vec<PgnLine>,
+}
+
+impl PgnMove {
+    pub fn new(m: Move) -> Self {
+        PgnMove {
+            m,
+            nags: vec![],
+            comment: None,
+            variations: vec![],
+        }
+    }
+}
+
+// 一串连续的着法，comment是第一步之前的注释
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PgnLine {
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PgnGame {
+    // 保持原来的顺序
+    pub tags: Vec<(String, String)>,
+    pub mainline: PgnLine,
+    pub result: GameResult,
+}
+
+#[derive(Debug)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Open,
+    Close,
+    Nag(u8),
+    Result(GameResult),
+    Move(String),
+}
+
+fn parse_tag(s: &str) -> Result<Token, PgnError> {
+    let invalid = || PgnError::InvalidTag(s.to_owned());
+    let (name, value) = s
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(invalid)?;
+    let value = value.trim();
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return Err(invalid());
+    }
+    let value = value[1..value.len() - 1]
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\");
+    Ok(Token::Tag(name.to_owned(), value))
+}
+
+// 着法后面的!、?等符号换成对应的NAG
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                let mut tag = String::new();
+                let mut quoted = false;
+                loop {
+                    match chars.next() {
+                        Some(']') if !quoted => break,
+                        Some('\\') if quoted => {
+                            tag.push('\\');
+                            tag.extend(chars.next());
+                        }
+                        Some(c) => {
+                            if c == '"' {
+                                quoted = !quoted;
+                            }
+                            tag.push(c);
+                        }
+                        None => return Err(PgnError::InvalidTag(tag)),
+                    }
+                }
+                tokens.push(parse_tag(&tag)?);
+            }
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(PgnError::UnterminatedComment),
+                    }
+                }
+                tokens.push(Token::Comment(comment.trim().to_owned()));
+            }
+            ';' => {
+                let comment = chars
+                    .by_ref()
+                    .take_while(|&c| c != '\n')
+                    .collect::<String>();
+                tokens.push(Token::Comment(comment.trim().to_owned()));
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "[]{}();".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.extend(word_tokens(&word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// 一个词可能是结果、NAG、回合数或着法，回合数可能和着法连在一起，例如1.H2-E2
+fn word_tokens(word: &str) -> Vec<Token> {
+    if let Some(result) = GameResult::parse(word) {
+        return vec![Token::Result(result)];
+    }
+    if let Some(nag) = word.strip_prefix('$') {
+        return nag.parse().map(Token::Nag).into_iter().collect();
+    }
+    let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let word = if digits > 0 && word[digits..].starts_with('.') {
+        word[digits..].trim_start_matches('.')
+    } else {
+        word
+    };
+    if word.is_empty() {
+        return vec![];
+    }
+    let text = word.trim_end_matches(['!', '?']);
+    let mut tokens = vec![Token::Move(text.to_owned())];
+    tokens.extend(suffix_nag(&word[text.len()..]).map(Token::Nag));
+    tokens
+}
+
+fn append_comment(comment: &mut Option<String>, text: String) {
+    match comment {
+        Some(c) => {
+            c.push(' ');
+            c.push_str(&text);
+        }
+        None => *comment = Some(text),
+    }
+}
+
+// 没有Format标签时依次尝试各种记法
+fn parse_move(
+    board: &mut Board,
+    s: &str,
+    notation: Option<Notation>,
+) -> Result<Move, MoveParseError> {
+    let iccs = IccsFormat::default();
+    match notation {
+        Some(notation) => notation.parse(board, s, &iccs),
+        None => [Notation::Iccs, Notation::Wxf, Notation::Chinese]
+            .iter()
+            .find_map(|n| n.parse(board, s, &iccs).ok())
+            .ok_or_else(|| MoveParseError::NoSuchMove(s.to_owned())),
+    }
+}
+
+// 解析一串着法直到变着结束、对局结束或者下一局的标签，返回时局面恢复原样
+fn parse_line(
+    board: &mut Board,
+    tokens: &mut Peekable<IntoIter<Token>>,
+    notation: Option<Notation>,
+    result: &mut GameResult,
+    nested: bool,
+) -> Result<PgnLine, PgnError> {
+    let mut line = PgnLine::default();
+    loop {
+        match tokens.peek() {
+            None | Some(Token::Tag(..)) if nested => return Err(PgnError::UnbalancedVariation),
+            None | Some(Token::Tag(..)) => break,
+            _ => {}
+        }
+        match tokens.next().unwrap() {
+            Token::Comment(c) => match line.moves.last_mut() {
+                Some(pm) => append_comment(&mut pm.comment, c),
+                None => append_comment(&mut line.comment, c),
+            },
+            Token::Nag(nag) => {
+                if let Some(pm) = line.moves.last_mut() {
+                    pm.nags.push(nag);
+                }
+            }
+            Token::Open => {
+                // 变着替代的是上一步，从上一步之前的局面开始
+                let last = match line.moves.last() {
+                    Some(pm) => pm.m,
+                    None => return Err(PgnError::UnbalancedVariation),
+                };
+                board.unapply_move(&last);
+                let variation = parse_line(board, tokens, notation, result, true)?;
+                board.apply_move(&last);
+                line.moves.last_mut().unwrap().variations.push(variation);
+            }
+            Token::Close if nested => break,
+            Token::Close => return Err(PgnError::UnbalancedVariation),
+            Token::Result(r) => {
+                if !nested {
+                    *result = r;
+                    break;
+                }
+            }
+            Token::Move(s) => {
+                let m = parse_move(board, &s, notation)?;
+                board.apply_move(&m);
+                line.moves.push(PgnMove::new(m));
+            }
+            Token::Tag(..) => unreachable!(),
+        }
+    }
+    for pm in line.moves.iter().rev() {
+        board.unapply_move(&pm.m);
+    }
+    Ok(line)
+}
+
+fn write_line(board: &mut Board, line: &PgnLine, notation: Notation, words: &mut Vec<String>) {
+    let iccs = IccsFormat::dashed();
+    if let Some(comment) = &line.comment {
+        words.push(format!("{{{}}}", comment));
+    }
+    // 黑方的着法前面也要写回合数，例如1... H9-G7
+    let mut need_number = true;
+    for pm in &line.moves {
+        if board.turn == Player::Red {
+            words.push(format!("{}.", board.fullmove_number));
+        } else if need_number {
+            words.push(format!("{}...", board.fullmove_number));
+        }
+        words.push(notation.format(board, &pm.m, &iccs));
+        words.extend(pm.nags.iter().map(|nag| format!("${}", nag)));
+        need_number = false;
+        if let Some(comment) = &pm.comment {
+            words.push(format!("{{{}}}", comment));
+            need_number = true;
+        }
+        for variation in &pm.variations {
+            words.push("(".to_owned());
+            write_line(board, variation, notation, words);
+            words.push(")".to_owned());
+            need_number = true;
+        }
+        board.apply_move(&pm.m);
+    }
+    for pm in line.moves.iter().rev() {
+        board.unapply_move(&pm.m);
+    }
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+    // FEN标签给出的初始局面，没有时为标准开局。不可能出现的局面也算错误，
+    // 棋子过多的局面会让走法生成出错
+    pub fn start_board(&self) -> Result<Board, FenError> {
+        match self.tag("FEN") {
+            Some(fen) => {
+                let board = Board::from_fen(fen)?;
+                board.validate().map_err(FenError::InvalidPosition)?;
+                Ok(board)
+            }
+            None => Ok(Board::init()),
+        }
+    }
+    // Format标签指定的记法
+    pub fn notation(&self) -> Option<Notation> {
+        match self.tag("Format")?.to_ascii_lowercase().as_str() {
+            "iccs" => Some(Notation::Iccs),
+            "wxf" => Some(Notation::Wxf),
+            "chinese" | "中文" => Some(Notation::Chinese),
+            _ => None,
+        }
+    }
+    // 主线的着法
+    pub fn moves(&self) -> Vec<Move> {
+        self.mainline.moves.iter().map(|pm| pm.m).collect()
+    }
+    // 只读取第一局
+    pub fn parse(text: &str) -> Result<Self, PgnError> {
+        Ok(PgnGame::parse_all(text)?
+            .into_iter()
+            .next()
+            .unwrap_or_default())
+    }
+    pub fn parse_all(text: &str) -> Result<Vec<Self>, PgnError> {
+        let mut tokens = tokenize(text)?.into_iter().peekable();
+        let mut games = vec![];
+        while tokens.peek().is_some() {
+            let mut game = PgnGame::default();
+            while let Some(Token::Tag(..)) = tokens.peek() {
+                if let Some(Token::Tag(name, value)) = tokens.next() {
+                    game.tags.push((name, value));
+                }
+            }
+            if let Some(result) = game.tag("Result").and_then(GameResult::parse) {
+                game.result = result;
+            }
+            let mut board = game.start_board()?;
+            let notation = game.notation();
+            game.mainline = parse_line(&mut board, &mut tokens, notation, &mut game.result, false)?;
+            games.push(game);
+        }
+        Ok(games)
+    }
+    // 按Format标签的记法输出，没有时用ICCS
+    pub fn to_pgn(&self) -> Result<String, FenError> {
+        let mut pgn = String::new();
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+        }
+        pgn.push('\n');
+        let mut board = self.start_board()?;
+        let mut words = vec![];
+        let notation = self.notation().unwrap_or(Notation::Iccs);
+        write_line(&mut board, &self.mainline, notation, &mut words);
+        words.push(self.result.to_string());
+        let mut width = 0;
+        for word in words {
+            let len = word.chars().count();
+            if width > 0 && width + 1 + len > LINE_WIDTH {
+                pgn.push('\n');
+                width = 0;
+            } else if width > 0 {
+                pgn.push(' ');
+                width += 1;
+            }
+            pgn.push_str(&word);
+            width += len;
+        }
+        pgn.push('\n');
+        Ok(pgn)
+    }
+}
+
+#[test]
+fn test_pgn_iccs() {
+    use crate::compact::CompactMove;
+
+    let text = r#"[Game "Chinese Chess"]
+[Event "测试 \"对局\""]
+[Red "红方"]
+[Black "黑方"]
+[Result "1-0"]
+[Format "ICCS"]
+
+{开局} 1. H2-E2 {中炮} H9-G7 2. H0-G2 (2. B0-C2 B9-C7) I9-H9 $1
+3. I0-H0 B7-B3!? (3... C6-C5 4. H0-H6 ; 过河车
+) 1-0
+"#;
+    let game = PgnGame::parse(text).unwrap();
+    assert_eq!(game.tag("Event"), Some("测试 \"对局\""));
+    assert_eq!(game.result, GameResult::RedWin);
+    assert_eq!(game.mainline.comment.as_deref(), Some("开局"));
+    let iccs = |moves: &[Move]| {
+        moves
+            .iter()
+            .map(|m| CompactMove::from(m).to_string())
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(
+        iccs(&game.moves()),
+        ["h2e2", "h9g7", "h0g2", "i9h9", "i0h0", "b7b3"]
+    );
+    let moves = &game.mainline.moves;
+    assert_eq!(moves[0].comment.as_deref(), Some("中炮"));
+    assert_eq!(moves[3].nags, [1]);
+    assert_eq!(moves[5].nags, [5]);
+    let variation = &moves[2].variations[0];
+    assert_eq!(
+        iccs(&variation.moves.iter().map(|pm| pm.m).collect::<Vec<Move>>()),
+        ["b0c2", "b9c7"]
+    );
+    let variation = &moves[5].variations[0];
+    assert_eq!(variation.moves[1].comment.as_deref(), Some("过河车"));
+
+    // 写出后再读回来应该一样
+    let pgn = game.to_pgn().unwrap();
+    assert!(pgn.contains("1. H2-E2 {中炮} 1... H9-G7 2. H0-G2 ( 2. B0-C2 B9-C7 ) 2... I9-H9 $1"));
+    assert_eq!(PgnGame::parse(&pgn).unwrap(), game);
+}
+
+#[test]
+fn test_pgn_notations() {
+    let fen = "3k5/9/9/9/9/9/9/9/9/4KR3 w - - 0 1";
+    let chinese = format!(
+        "[FEN \"{}\"]\n[Format \"Chinese\"]\n\n1. 车四进九 将4进1 2. 车四退一 *\n",
+        fen
+    );
+    let game = PgnGame::parse(&chinese).unwrap();
+    assert_eq!(game.moves().len(), 3);
+    assert_eq!(game.result, GameResult::Unknown);
+    assert_eq!(game.to_pgn().unwrap(), chinese);
+
+    // 改成WXF格式输出
+    let mut wxf = game.clone();
+    wxf.set_tag("Format", "WXF");
+    let text = wxf.to_pgn().unwrap();
+    assert!(text.contains("1. R4+9 K4+1 2. R4-1 *"));
+    assert_eq!(PgnGame::parse(&text).unwrap().moves(), game.moves());
+
+    // 没有Format标签时自动识别
+    let text = format!("[FEN \"{}\"]\n\n1.R4+9 将4进1 2.f9f8", fen);
+    assert_eq!(PgnGame::parse(&text).unwrap().moves(), game.moves());
+}
+
+#[test]
+fn test_pgn_games() {
+    use crate::{
+        board::{Chess, ChessType},
+        error::PositionError,
+    };
+
+    let text =
+        "[Event \"1\"]\n\n1. H2-E2 H9-G7 1-0\n\n[Event \"2\"]\n\n1. B2-E2 0-1\n[Event \"3\"]\n";
+    let games = PgnGame::parse_all(text).unwrap();
+    assert_eq!(games.len(), 3);
+    assert_eq!(games[0].moves().len(), 2);
+    assert_eq!(games[1].result, GameResult::BlackWin);
+    assert_eq!(games[2].tag("Event"), Some("3"));
+    assert!(games[2].moves().is_empty());
+
+    assert_eq!(
+        PgnGame::parse("1. H2-E2 (1. B2-E2"),
+        Err(PgnError::UnbalancedVariation)
+    );
+    assert_eq!(
+        PgnGame::parse("1. H2-E2 H9-G7)"),
+        Err(PgnError::UnbalancedVariation)
+    );
+    assert_eq!(
+        PgnGame::parse("1. H2-E2 {没有结束"),
+        Err(PgnError::UnterminatedComment)
+    );
+    assert_eq!(
+        PgnGame::parse("[Format \"ICCS\"]\n1. H2-E3"),
+        Err(PgnError::Move(MoveParseError::NoSuchMove(
+            "H2-E3".to_owned()
+        )))
+    );
+    assert_eq!(
+        PgnGame::parse("[Event]"),
+        Err(PgnError::InvalidTag("Event".to_owned()))
+    );
+    // FEN标签里不可能出现的局面，棋子太多时走法生成会出错
+    let pgn = "[FEN \"R8/1R2k4/2R6/6R2/7R1/8R/3R5/5R3/4R4/4K4 w - - 0 1\"]\n1. A9-A8";
+    assert!(matches!(
+        PgnGame::parse(pgn),
+        Err(PgnError::Fen(FenError::InvalidPosition(errors)))
+            if errors.contains(&PositionError::TooMany(Chess::Red(ChessType::Rook), 9))
+    ));
+    let game = PgnGame {
+        tags: vec![(
+            "FEN".to_owned(),
+            "4k4/9/9/9/9/9/9/9/9/4K4 w - - 0 1".to_owned(),
+        )],
+        ..PgnGame::default()
+    };
+    assert_eq!(
+        game.start_board().err(),
+        Some(FenError::InvalidPosition(vec![PositionError::FacingKings]))
+    );
+}
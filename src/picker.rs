@@ -0,0 +1,168 @@
+use crate::{
+    board::{Board, Chess, Move},
+    compact::CompactMove,
+    movegen::{GenType, MoveList},
+};
+
+// 着法的分阶段生成顺序
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+enum Stage {
+    HashMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// 分阶段给出着法：置换表着法、好的吃子、杀手着法、按历史表排序的不吃子着法，
+// 最后是亏本的吃子。只有前一个阶段用完了才生成下一个阶段的着法，
+// 如果前面的着法已经产生截断，后面的着法就不用生成了
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<CompactMove>,
+    killers: [Option<CompactMove>; 2],
+    moves: MoveList,
+    bad_captures: MoveList,
+    index: usize,
+}
+
+// 吃子的子力不小于用来吃子的棋子，认为是好的吃子
+fn is_good_capture(m: &Move) -> bool {
+    m.capture.value() >= m.chess.value()
+}
+
+impl MovePicker {
+    pub fn new(hash_move: Option<CompactMove>, killers: [Option<CompactMove>; 2]) -> Self {
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            moves: MoveList::new(),
+            bad_captures: MoveList::new(),
+            index: 0,
+        }
+    }
+    // 前面阶段已经给出过的着法不再重复
+    fn is_picked(&self, m: CompactMove) -> bool {
+        let m = Some(m);
+        self.hash_move == m || (self.stage > Stage::Killers && self.killers.contains(&m))
+    }
+    pub fn next(&mut self, board: &mut Board) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if let Some(m) = self.hash_move.map(|m| m.to_move(board)) {
+                        if board.is_legal(&m) {
+                            return Some(m);
+                        }
+                        self.hash_move = None;
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    self.moves = board.generate_legal_move(GenType::Captures);
+                    self.index = 0;
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => {
+                    while self.index < self.moves.len() {
+                        let cm = self.moves[self.index];
+                        self.index += 1;
+                        if self.is_picked(cm) {
+                            continue;
+                        }
+                        let m = cm.to_move(board);
+                        if is_good_capture(&m) {
+                            return Some(m);
+                        }
+                        self.bad_captures.push(cm);
+                    }
+                    self.index = 0;
+                    self.stage = Stage::Killers;
+                }
+                Stage::Killers => {
+                    while self.index < self.killers.len() {
+                        let killer = self.killers[self.index];
+                        self.index += 1;
+                        if let Some(m) = killer.map(|m| m.to_move(board)) {
+                            if m.capture == Chess::None
+                                && self.hash_move != killer
+                                && board.is_legal(&m)
+                            {
+                                return Some(m);
+                            }
+                        }
+                    }
+                    self.stage = Stage::GenerateQuiets;
+                }
+                Stage::GenerateQuiets => {
+                    self.moves = board.generate_legal_move(GenType::Quiets);
+                    self.moves.sort_by_key(|&m| -board.history_value(m));
+                    self.index = 0;
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    while self.index < self.moves.len() {
+                        let m = self.moves[self.index];
+                        self.index += 1;
+                        if !self.is_picked(m) {
+                            return Some(m.to_move(board));
+                        }
+                    }
+                    self.index = 0;
+                    self.stage = Stage::BadCaptures;
+                }
+                Stage::BadCaptures => {
+                    if self.index < self.bad_captures.len() {
+                        self.index += 1;
+                        return Some(self.bad_captures[self.index - 1].to_move(board));
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+#[test]
+fn test_move_picker() {
+    let mut board =
+        Board::from_fen("r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1")
+            .unwrap();
+    let legal = board.legal_moves();
+    let hash_move = legal[legal.len() - 1];
+    let mut picker = MovePicker::new(
+        Some(CompactMove::from(hash_move)),
+        [Some(CompactMove::from(legal[3])), None],
+    );
+    let mut picked = vec![];
+    while let Some(m) = picker.next(&mut board) {
+        picked.push(m);
+    }
+    // 置换表着法最先给出，每个合法着法都恰好出现一次
+    assert_eq!(picked[0], hash_move);
+    assert_eq!(picked.len(), legal.len());
+    for m in legal.iter() {
+        assert_eq!(picked.iter().filter(|p| *p == m).count(), 1);
+    }
+}
+
+#[test]
+fn test_move_picker_illegal_hash_move() {
+    let mut board = Board::init();
+    // 起点上没有棋子，或者走法不合规则
+    let empty = CompactMove::from_iccs("e5e4").unwrap();
+    let illegal = CompactMove::from_iccs("a0a5").unwrap();
+    let mut picker = MovePicker::new(Some(empty), [Some(illegal), None]);
+    let mut count = 0;
+    while let Some(m) = picker.next(&mut board) {
+        assert_ne!(CompactMove::from(m), empty);
+        assert_ne!(CompactMove::from(m), illegal);
+        count += 1;
+    }
+    assert_eq!(count, 44);
+}
@@ -0,0 +1,203 @@
+use crate::board::{Board, Chess, ChessType, Move, Player, Position, BOARD_HEIGHT, BOARD_WIDTH};
+
+const RED: &str = "\x1b[1;31m";
+const BLACK: &str = "\x1b[1m";
+// 上一步的起点和终点反色显示
+const HIGHLIGHT: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+// 棋子的写法
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Glyphs {
+    // 帥仕相傌俥炮兵 / 將士象馬車砲卒，每个字占两列
+    Chinese,
+    // FEN里的字母，红方大写，黑方小写
+    Ascii,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RenderOptions {
+    pub glyphs: Glyphs,
+    // 使用ANSI颜色
+    pub color: bool,
+    pub coordinates: bool,
+    pub last_move: Option<Move>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            glyphs: Glyphs::Chinese,
+            color: false,
+            coordinates: true,
+            last_move: None,
+        }
+    }
+}
+
+pub fn chinese_glyph(chess: Chess) -> Option<char> {
+    let c = match chess {
+        Chess::Red(ct) => match ct {
+            ChessType::King => '帥',
+            ChessType::Advisor => '仕',
+            ChessType::Bishop => '相',
+            ChessType::Knight => '傌',
+            ChessType::Rook => '俥',
+            ChessType::Cannon => '炮',
+            ChessType::Pawn => '兵',
+        },
+        Chess::Black(ct) => match ct {
+            ChessType::King => '將',
+            ChessType::Advisor => '士',
+            ChessType::Bishop => '象',
+            ChessType::Knight => '馬',
+            ChessType::Rook => '車',
+            ChessType::Cannon => '砲',
+            ChessType::Pawn => '卒',
+        },
+        Chess::None => return None,
+    };
+    Some(c)
+}
+
+// 九宫的斜线，row和row+1之间，col和col+1之间
+fn diagonal(row: i32, col: i32) -> char {
+    let palace = matches!(row, 0 | 1 | 7 | 8) && (col == 3 || col == 4);
+    // 九宫里第一行和第二行之间是\/，第二行和第三行之间是/\
+    let second = row == 1 || row == 8;
+    if !palace {
+        ' '
+    } else if second == (col == 3) {
+        '/'
+    } else {
+        '\\'
+    }
+}
+
+impl RenderOptions {
+    // 每个交叉点占两列，汉字正好两列，字母和空点后面补一个横线
+    fn point(&self, board: &Board, pos: Position) -> String {
+        let chess = board.chess_at(pos);
+        let last = self.last_move.filter(|m| m.from == pos || m.to == pos);
+        let (text, fill) = match (self.glyphs, chess.player()) {
+            (Glyphs::Chinese, Some(_)) => (chinese_glyph(chess).unwrap().to_string(), ""),
+            (Glyphs::Ascii, Some(_)) => (chess.fen_char().unwrap().to_string(), "-"),
+            // 没有颜色时用*标出上一步的起点
+            (_, None) if last.is_some() && !self.color => ("*".to_owned(), "-"),
+            (_, None) => ("+".to_owned(), "-"),
+        };
+        let fill = if pos.col == BOARD_WIDTH - 1 { "" } else { fill };
+        if !self.color {
+            return text + fill;
+        }
+        let mut styled = String::new();
+        if last.is_some() {
+            styled.push_str(HIGHLIGHT);
+        }
+        match chess.player() {
+            Some(Player::Red) => styled.push_str(RED),
+            Some(Player::Black) => styled.push_str(BLACK),
+            None => {}
+        }
+        if styled.is_empty() {
+            text + fill
+        } else {
+            format!("{}{}{}{}", styled, text, RESET, fill)
+        }
+    }
+    fn river(&self) -> String {
+        // 两条竖线之间有31列
+        match self.glyphs {
+            Glyphs::Chinese => format!(
+                "|{}楚 河{}漢 界{}|",
+                " ".repeat(6),
+                " ".repeat(8),
+                " ".repeat(7)
+            ),
+            Glyphs::Ascii => format!("|{}river{}|", " ".repeat(13), " ".repeat(13)),
+        }
+    }
+}
+
+impl Board {
+    // 画出棋盘，行号是ICCS坐标，红方在下
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let margin = if options.coordinates { "  " } else { "" };
+        let mut lines = vec![];
+        for row in 0..BOARD_HEIGHT {
+            let mut line = String::new();
+            if options.coordinates {
+                line.push_str(&format!("{} ", BOARD_HEIGHT - 1 - row));
+            }
+            for col in 0..BOARD_WIDTH {
+                line.push_str(&options.point(self, Position::new(row, col)));
+                if col < BOARD_WIDTH - 1 {
+                    line.push_str("--");
+                }
+            }
+            lines.push(line);
+            if row == BOARD_HEIGHT / 2 - 1 {
+                lines.push(format!("{}{}", margin, options.river()));
+            } else if row < BOARD_HEIGHT - 1 {
+                let mut line = margin.to_owned();
+                for col in 0..BOARD_WIDTH {
+                    line.push('|');
+                    if col < BOARD_WIDTH - 1 {
+                        line.push(' ');
+                        line.push(diagonal(row, col));
+                        line.push(' ');
+                    }
+                }
+                lines.push(line);
+            }
+        }
+        if options.coordinates {
+            let files = (0..BOARD_WIDTH)
+                .map(|col| ((b'a' + col as u8) as char).to_string())
+                .collect::<Vec<String>>();
+            lines.push(format!("{}{}", margin, files.join("   ")));
+        }
+        lines.join("\n")
+    }
+}
+
+#[test]
+fn test_render() {
+    use crate::compact::CompactMove;
+
+    let mut board = Board::init();
+    let text = board.render(&RenderOptions::default());
+    let lines = text.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 20);
+    assert_eq!(lines[0], "9 車--馬--象--士--將--士--象--馬--車");
+    assert_eq!(lines[1], "  |   |   |   | \\ | / |   |   |   |");
+    assert_eq!(lines[3], "  |   |   |   | / | \\ |   |   |   |");
+    assert_eq!(lines[9], "  |      楚 河        漢 界       |");
+    assert_eq!(lines[14], "2 +---炮--+---+---+---+---+---炮--+");
+    assert_eq!(lines[19], "  a   b   c   d   e   f   g   h   i");
+
+    let m = CompactMove::from_iccs("h2e2").unwrap().to_move(&board);
+    board.apply_move(&m);
+    let options = RenderOptions {
+        glyphs: Glyphs::Ascii,
+        coordinates: false,
+        last_move: Some(m),
+        ..RenderOptions::default()
+    };
+    let text = board.render(&options);
+    let lines = text.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 19);
+    assert_eq!(lines[0], "r---n---b---a---k---a---b---n---r");
+    assert_eq!(lines[9], "|             river             |");
+    assert_eq!(lines[14], "+---C---+---+---C---+---+---*---+");
+
+    // 彩色输出，上一步的终点反色
+    let options = RenderOptions {
+        color: true,
+        ..options
+    };
+    let text = board.render(&options);
+    assert!(text.contains(
+        "+---\x1b[1;31mC\x1b[0m---+---+---\x1b[7m\x1b[1;31mC\x1b[0m---+---+---\x1b[7m+\x1b[0m---"
+    ));
+}
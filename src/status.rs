@@ -0,0 +1,121 @@
+use crate::{
+    board::{Board, Move, Player},
+    constant::MOVE_LIMIT,
+};
+
+// 对局是否结束，由谁负
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameStatus {
+    Ongoing,
+    // 轮到走棋的一方被将死
+    Checkmate,
+    // 轮到走棋的一方无子可走，象棋里困毙算负
+    Stalemate,
+    DrawByRepetition,
+    // 连续MOVE_LIMIT个半回合没有吃子
+    DrawByMoveLimit,
+    // 这一方在重复局面里一直将军，长将判负
+    LossByPerpetualCheck(Player),
+}
+
+impl Board {
+    // 根据当前局面和position_history判断对局状态，只处理长将，不处理长捉
+    pub fn game_status(&mut self) -> GameStatus {
+        if self.legal_moves().is_empty() {
+            return if self.is_checked(self.turn) {
+                GameStatus::Checkmate
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+        // 上次吃子以后的局面才可能重复
+        let len = self.position_history.len();
+        let start = len - (self.halfmove_clock.max(0) as usize).min(len);
+        let repeated = (start..len)
+            .filter(|&i| self.position_history[i].0 == self.zobrist_value)
+            .collect::<Vec<usize>>();
+        // 当前局面第三次出现
+        if repeated.len() >= 2 {
+            let first = repeated[repeated.len() - 2];
+            return match self.perpetual_checker(first) {
+                Some(player) => GameStatus::LossByPerpetualCheck(player),
+                None => GameStatus::DrawByRepetition,
+            };
+        }
+        if self.halfmove_clock >= MOVE_LIMIT {
+            return GameStatus::DrawByMoveLimit;
+        }
+        GameStatus::Ongoing
+    }
+    // 从第first步到现在，只有一方每步都在将军时返回这一方
+    fn perpetual_checker(&mut self, first: usize) -> Option<Player> {
+        let moves = self.position_history[first..]
+            .iter()
+            .map(|&(_, m)| m)
+            .collect::<Vec<Move>>();
+        // 从后往前退回去，记下每一步之后对方是否被将军
+        let mut always_check = [true, true];
+        for m in moves.iter().rev() {
+            if !self.is_checked(m.player.next()) {
+                always_check[m.player.value() as usize] = false;
+            }
+            self.unapply_move(m);
+        }
+        for m in &moves {
+            self.apply_move(m);
+        }
+        match always_check {
+            [true, false] => Some(Player::Red),
+            [false, true] => Some(Player::Black),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_game_status() {
+    use crate::compact::CompactMove;
+
+    let play = |board: &mut Board, moves: &str| {
+        for iccs in moves.split_whitespace() {
+            let m = CompactMove::from_iccs(iccs).unwrap().to_move(board);
+            board.apply_move(&m);
+        }
+    };
+
+    let mut board = Board::init();
+    assert_eq!(board.game_status(), GameStatus::Ongoing);
+    // 将死和困毙
+    let mut board = Board::from_fen("R3k4/4R4/9/9/9/9/9/9/9/4K4 b - - 0 1").unwrap();
+    assert_eq!(board.game_status(), GameStatus::Checkmate);
+    let mut board = Board::from_fen("3k5/R8/9/9/9/9/9/9/4R4/5K3 b - - 0 1").unwrap();
+    assert_eq!(board.game_status(), GameStatus::Stalemate);
+
+    // 双方来回跳马，开局局面第三次出现
+    let mut board = Board::init();
+    play(&mut board, "h0g2 h9g7 g2h0 g7h9");
+    assert_eq!(board.game_status(), GameStatus::Ongoing);
+    play(&mut board, "h0g2 h9g7 g2h0 g7h9");
+    assert_eq!(board.game_status(), GameStatus::DrawByRepetition);
+    assert_eq!(
+        board.to_fen(),
+        Board::init().to_fen().replace(" 0 1", " 8 5")
+    );
+
+    // 红车一直将军
+    let mut board = Board::from_fen("9/4k4/R8/9/9/9/9/9/9/3K5 w - - 0 1").unwrap();
+    play(&mut board, "a7a8 e8e9 a8a9 e9e8 a9a8 e8e9 a8a9 e9e8");
+    assert_eq!(board.game_status(), GameStatus::Ongoing);
+    play(&mut board, "a9a8");
+    let fen = board.to_fen();
+    assert_eq!(
+        board.game_status(),
+        GameStatus::LossByPerpetualCheck(Player::Red)
+    );
+    // 判断时退回去的着法要走回来
+    assert_eq!(board.to_fen(), fen);
+    assert_eq!(board.position_history.len(), 9);
+
+    let mut board = Board::from_fen("4k4/9/9/9/9/9/9/9/9/3K5 w - - 120 80").unwrap();
+    assert_eq!(board.game_status(), GameStatus::DrawByMoveLimit);
+}
@@ -0,0 +1,305 @@
+use crate::{
+    board::{Board, Move, Player, Position, BOARD_HEIGHT, BOARD_WIDTH},
+    game::Game,
+    render::chinese_glyph,
+};
+
+const BOARD_COLOR: &str = "#f0d9a0";
+const LINE_COLOR: &str = "#5a3d1e";
+const PIECE_COLOR: &str = "#fbecc8";
+const RED_COLOR: &str = "#c8102e";
+const BLACK_COLOR: &str = "#1a1a1a";
+const ARROW_COLOR: &str = "#2e8b57";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SvgOptions {
+    // 相邻两个交叉点的距离，像素
+    pub cell: f64,
+    pub coordinates: bool,
+    // 要画箭头的着法，(起点, 终点)
+    pub arrows: Vec<(Position, Position)>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            cell: 40.0,
+            coordinates: true,
+            arrows: vec![],
+        }
+    }
+}
+
+impl SvgOptions {
+    // 棋盘四周留出一格，放坐标和半个棋子
+    fn point(&self, pos: Position) -> (f64, f64) {
+        (
+            self.cell * (pos.col + 1) as f64,
+            self.cell * (pos.row + 1) as f64,
+        )
+    }
+    fn line(&self, svg: &mut String, a: Position, b: Position) {
+        let (x1, y1) = self.point(a);
+        let (x2, y2) = self.point(b);
+        svg.push_str(&format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+            x1, y1, x2, y2
+        ));
+    }
+    // svg开头、箭头的定义和不变的棋盘
+    fn background(&self) -> String {
+        let c = self.cell;
+        let (width, height) = (c * (BOARD_WIDTH + 1) as f64, c * (BOARD_HEIGHT + 1) as f64);
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+            width, height
+        );
+        svg.push_str(&format!(
+            "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"5\" refY=\"5\" markerWidth=\"4\" markerHeight=\"4\" orient=\"auto\"><path d=\"M0,0L10,5L0,10z\" fill=\"{}\"/></marker></defs>\n",
+            ARROW_COLOR
+        ));
+        svg.push_str(&format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            width, height, BOARD_COLOR
+        ));
+        svg.push_str(&format!(
+            "<g stroke=\"{}\" stroke-width=\"{}\">",
+            LINE_COLOR,
+            c / 40.0
+        ));
+        for row in 0..BOARD_HEIGHT {
+            self.line(
+                &mut svg,
+                Position::new(row, 0),
+                Position::new(row, BOARD_WIDTH - 1),
+            );
+        }
+        // 中间的竖线在河界断开
+        for col in 0..BOARD_WIDTH {
+            if col == 0 || col == BOARD_WIDTH - 1 {
+                self.line(
+                    &mut svg,
+                    Position::new(0, col),
+                    Position::new(BOARD_HEIGHT - 1, col),
+                );
+            } else {
+                self.line(&mut svg, Position::new(0, col), Position::new(4, col));
+                self.line(
+                    &mut svg,
+                    Position::new(5, col),
+                    Position::new(BOARD_HEIGHT - 1, col),
+                );
+            }
+        }
+        for top in [0, 7] {
+            self.line(&mut svg, Position::new(top, 3), Position::new(top + 2, 5));
+            self.line(&mut svg, Position::new(top, 5), Position::new(top + 2, 3));
+        }
+        svg.push_str("</g>\n");
+        let (_, river) = self.point(Position::new(4, 0));
+        svg.push_str(&format!(
+            "<g font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">",
+            c * 0.5,
+            LINE_COLOR
+        ));
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\">楚 河</text><text x=\"{}\" y=\"{}\">漢 界</text>",
+            c * 3.0,
+            river + c / 2.0,
+            c * 7.0,
+            river + c / 2.0
+        ));
+        if self.coordinates {
+            for col in 0..BOARD_WIDTH {
+                let (x, _) = self.point(Position::new(0, col));
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"{}\">{}</text>",
+                    x,
+                    c * (BOARD_HEIGHT as f64 + 0.6),
+                    c * 0.35,
+                    (b'a' + col as u8) as char
+                ));
+            }
+            for row in 0..BOARD_HEIGHT {
+                let (_, y) = self.point(Position::new(row, 0));
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"{}\">{}</text>",
+                    c * 0.4,
+                    y,
+                    c * 0.35,
+                    BOARD_HEIGHT - 1 - row
+                ));
+            }
+        }
+        svg.push_str("</g>\n");
+        svg
+    }
+    // 棋子和箭头
+    fn pieces(&self, board: &Board, arrows: &[(Position, Position)]) -> String {
+        let c = self.cell;
+        let mut svg = String::new();
+        for row in 0..BOARD_HEIGHT {
+            for col in 0..BOARD_WIDTH {
+                let pos = Position::new(row, col);
+                let chess = board.chess_at(pos);
+                let color = match chess.player() {
+                    Some(Player::Red) => RED_COLOR,
+                    Some(Player::Black) => BLACK_COLOR,
+                    None => continue,
+                };
+                let (x, y) = self.point(pos);
+                svg.push_str(&format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+                    x,
+                    y,
+                    c * 0.45,
+                    PIECE_COLOR,
+                    color,
+                    c / 20.0
+                ));
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                    x,
+                    y,
+                    c * 0.6,
+                    color,
+                    chinese_glyph(chess).unwrap()
+                ));
+            }
+        }
+        for &(from, to) in arrows {
+            let (x1, y1) = self.point(from);
+            let (x2, y2) = self.point(to);
+            svg.push_str(&format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\" stroke-opacity=\"0.8\" marker-end=\"url(#arrow)\"/>\n",
+                x1,
+                y1,
+                x2,
+                y2,
+                ARROW_COLOR,
+                c / 8.0
+            ));
+        }
+        svg
+    }
+}
+
+impl Board {
+    // 不依赖其他库生成SVG棋图
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let mut svg = options.background();
+        svg.push_str(&options.pieces(self, &options.arrows));
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+impl Game {
+    // 沿主线逐步回放的动画SVG，每一帧是一个局面，按时间轮流显示，最后一帧之后从头循环
+    pub fn to_animated_svg(&self, options: &SvgOptions, seconds_per_move: f64) -> String {
+        let mut board = self.initial_board();
+        let moves = self.mainline();
+        let frames = moves.len() + 1;
+        let duration = seconds_per_move * frames as f64;
+        let mut svg = options.background();
+        let mut last: Option<Move> = None;
+        for frame in 0..frames {
+            if frame > 0 {
+                let m = moves[frame - 1];
+                board.apply_move(&m);
+                last = Some(m);
+            }
+            let mut arrows = options.arrows.clone();
+            arrows.extend(last.map(|m| (m.from, m.to)));
+            // 离散的visibility动画，keyTimes是这一帧出现和消失的时刻
+            let start = frame as f64 / frames as f64;
+            let end = (frame + 1) as f64 / frames as f64;
+            let (values, key_times) = if frames == 1 {
+                ("visible".to_owned(), "0".to_owned())
+            } else if frame == 0 {
+                ("visible;hidden".to_owned(), format!("0;{}", end))
+            } else if frame == frames - 1 {
+                ("hidden;visible".to_owned(), format!("0;{}", start))
+            } else {
+                (
+                    "hidden;visible;hidden".to_owned(),
+                    format!("0;{};{}", start, end),
+                )
+            };
+            svg.push_str(&format!(
+                "<g class=\"frame\" visibility=\"{}\"><animate attributeName=\"visibility\" values=\"{}\" keyTimes=\"{}\" dur=\"{}s\" calcMode=\"discrete\" repeatCount=\"indefinite\"/>\n",
+                if frame == 0 { "visible" } else { "hidden" },
+                values,
+                key_times,
+                duration
+            ));
+            svg.push_str(&options.pieces(&board, &arrows));
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+#[test]
+fn test_svg() {
+    use crate::compact::CompactMove;
+
+    let board = Board::init();
+    let m = CompactMove::from_iccs("h2e2").unwrap().to_move(&board);
+    let options = SvgOptions {
+        arrows: vec![(m.from, m.to)],
+        ..SvgOptions::default()
+    };
+    let svg = board.to_svg(&options);
+    assert!(
+        svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"400\" height=\"440\"")
+    );
+    assert!(svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("<circle").count(), 32);
+    assert!(svg.contains(
+        "fill=\"#c8102e\" text-anchor=\"middle\" dominant-baseline=\"central\">帥</text>"
+    ));
+    assert!(svg.contains("<line x1=\"320\" y1=\"320\" x2=\"200\" y2=\"320\" stroke=\"#2e8b57\""));
+    // 10条横线，2条边线，7条竖线各分两段，4条斜线
+    let grid = svg.split("<g stroke").nth(1).unwrap();
+    assert_eq!(
+        grid.split("</g>").next().unwrap().matches("<line").count(),
+        30
+    );
+    assert_eq!(svg.matches("<g").count(), svg.matches("</g>").count());
+
+    let svg = Board::from_fen("3k5/9/9/9/9/9/9/9/9/4KR3 w - - 0 1")
+        .unwrap()
+        .to_svg(&SvgOptions {
+            coordinates: false,
+            ..SvgOptions::default()
+        });
+    assert_eq!(svg.matches("<circle").count(), 3);
+    assert!(!svg.contains(">a</text>"));
+}
+
+#[test]
+fn test_animated_svg() {
+    use crate::compact::CompactMove;
+
+    let mut game = Game::new(Board::init());
+    for iccs in ["h2e2", "h9g7", "h0g2"] {
+        let m = CompactMove::from_iccs(iccs).unwrap().to_move(game.board());
+        game.add_move(m).unwrap();
+    }
+    let svg = game.to_animated_svg(&SvgOptions::default(), 1.5);
+    assert_eq!(svg.matches("<g class=\"frame\"").count(), 4);
+    assert_eq!(svg.matches("dur=\"6s\"").count(), 4);
+    assert!(svg.contains("values=\"visible;hidden\" keyTimes=\"0;0.25\""));
+    assert!(svg.contains("values=\"hidden;visible;hidden\" keyTimes=\"0;0.25;0.5\""));
+    assert!(svg.contains("values=\"hidden;visible\" keyTimes=\"0;0.75\""));
+    // 除了第一帧，每帧都有上一步的箭头
+    assert_eq!(svg.matches("marker-end").count(), 3);
+    assert_eq!(svg.matches("<circle").count(), 32 * 4);
+    assert_eq!(svg.matches("<g").count(), svg.matches("</g>").count());
+
+    // 没有着法时只有一帧
+    let svg = Game::new(Board::init()).to_animated_svg(&SvgOptions::default(), 1.0);
+    assert!(svg.contains("values=\"visible\" keyTimes=\"0\""));
+}
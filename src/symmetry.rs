@@ -0,0 +1,143 @@
+use crate::{
+    board::{Board, Chess, Move, Position, BOARD_HEIGHT, BOARD_WIDTH},
+    compact::CompactMove,
+    constant::{ZOBRIST_TABLE, ZOBRIST_TABLE_LOCK},
+};
+
+impl Position {
+    // 左右镜像，a线和i线互换
+    pub fn mirror_lr(&self) -> Self {
+        Position::new(self.row, BOARD_WIDTH - 1 - self.col)
+    }
+    // 上下镜像，红黑双方的底线互换
+    pub fn mirror_ud(&self) -> Self {
+        Position::new(BOARD_HEIGHT - 1 - self.row, self.col)
+    }
+}
+
+impl Chess {
+    // 换成对方同一种棋子
+    pub fn flip_color(&self) -> Chess {
+        match (self.player(), self.chess_type()) {
+            (Some(player), Some(ct)) => Chess::of(player.next(), ct),
+            _ => Chess::None,
+        }
+    }
+}
+
+impl Move {
+    pub fn mirror_lr(&self) -> Move {
+        Move {
+            from: self.from.mirror_lr(),
+            to: self.to.mirror_lr(),
+            ..*self
+        }
+    }
+    // 和Board::flip_colors对应，棋盘上下翻转，红黑互换
+    pub fn flip_colors(&self) -> Move {
+        Move {
+            player: self.player.next(),
+            from: self.from.mirror_ud(),
+            to: self.to.mirror_ud(),
+            chess: self.chess.flip_color(),
+            capture: self.capture.flip_color(),
+        }
+    }
+}
+
+impl CompactMove {
+    pub fn mirror_lr(self) -> Self {
+        CompactMove::new(self.source().mirror_lr(), self.target().mirror_lr())
+    }
+    pub fn flip_colors(self) -> Self {
+        CompactMove::new(self.source().mirror_ud(), self.target().mirror_ud())
+    }
+}
+
+impl Board {
+    // 按f把每个棋子搬到新的位置，回合数照抄，着法历史不保留
+    fn transformed<F>(&self, f: F, turn_flipped: bool) -> Board
+    where
+        F: Fn(Position, Chess) -> (Position, Chess),
+    {
+        let mut board = Board::empty();
+        for row in 0..BOARD_HEIGHT {
+            for col in 0..BOARD_WIDTH {
+                let pos = Position::new(row, col);
+                let chess = self.chess_at(pos);
+                if chess != Chess::None {
+                    let (pos, chess) = f(pos, chess);
+                    board.set_chess(pos, chess);
+                }
+            }
+        }
+        board.turn = if turn_flipped {
+            self.turn.next()
+        } else {
+            self.turn
+        };
+        board.halfmove_clock = self.halfmove_clock;
+        board.fullmove_number = self.fullmove_number;
+        board.zobrist_value = ZOBRIST_TABLE.calc_board(&board.chesses, board.turn);
+        board.zobrist_value_lock = ZOBRIST_TABLE_LOCK.calc_board(&board.chesses, board.turn);
+        board.values = board.calc_values();
+        board
+    }
+    // 左右镜像的局面，着法和评估都对称
+    pub fn mirror_lr(&self) -> Board {
+        self.transformed(|pos, chess| (pos.mirror_lr(), chess), false)
+    }
+    // 红黑互换并上下翻转，轮到走棋的一方也互换，局面对走棋方来说不变
+    pub fn flip_colors(&self) -> Board {
+        self.transformed(|pos, chess| (pos.mirror_ud(), chess.flip_color()), true)
+    }
+}
+
+#[test]
+fn test_symmetry() {
+    let mut board =
+        Board::from_fen("rnbakab1r/9/1c4nc1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR w - - 2 2")
+            .unwrap();
+    let mirrored = board.mirror_lr();
+    assert_eq!(
+        mirrored.to_fen(),
+        "r1bakabnr/9/1cn4c1/p1p1p1p1p/9/9/P1P1P1P1P/4C2C1/9/RNBAKABNR w - - 2 2"
+    );
+    assert_eq!(mirrored.mirror_lr().to_fen(), board.to_fen());
+    assert_eq!(mirrored.values, board.values);
+    let flipped = board.flip_colors();
+    assert_eq!(
+        flipped.to_fen(),
+        "rnbakabnr/9/1c2c4/p1p1p1p1p/9/9/P1P1P1P1P/1C4NC1/9/RNBAKAB1R b - - 2 2"
+    );
+    assert_eq!(flipped.flip_colors().to_fen(), board.to_fen());
+    assert_eq!(flipped.values, [board.values[1], board.values[0]]);
+
+    // 镜像局面的合法着法正好是原局面着法的镜像
+    let mut mirrored = board.mirror_lr();
+    let mut flipped = board.flip_colors();
+    let mut expected = board
+        .legal_moves()
+        .iter()
+        .map(|m| CompactMove::from(m.mirror_lr()).0)
+        .collect::<Vec<u16>>();
+    let mut actual = mirrored
+        .legal_moves()
+        .iter()
+        .map(|m| CompactMove::from(m).0)
+        .collect::<Vec<u16>>();
+    expected.sort_unstable();
+    actual.sort_unstable();
+    assert_eq!(expected, actual);
+    for m in board.legal_moves() {
+        assert!(flipped.is_legal(&m.flip_colors()));
+        assert_eq!(
+            CompactMove::from(m).flip_colors(),
+            CompactMove::from(m.flip_colors())
+        );
+    }
+
+    let m = CompactMove::from_iccs("h2e2").unwrap();
+    assert_eq!(m.mirror_lr().to_iccs(), "b2e2");
+    assert_eq!(m.flip_colors().to_iccs(), "h7e7");
+}
@@ -0,0 +1,176 @@
+use crate::{
+    board::{
+        in_country, in_palace, Board, Chess, ChessType, Player, Position, BOARD_HEIGHT, BOARD_WIDTH,
+    },
+    error::PositionError,
+};
+
+// 红方相能到的7个位置，黑方按行翻转
+const BISHOP_SQUARES: [(i32, i32); 7] = [(9, 2), (9, 6), (7, 0), (7, 4), (7, 8), (5, 2), (5, 6)];
+
+// 每种棋子最多的个数
+fn max_count(ct: ChessType) -> usize {
+    match ct {
+        ChessType::King => 1,
+        ChessType::Pawn => 5,
+        _ => 2,
+    }
+}
+
+// 棋子能否出现在这个位置
+fn legal_square(player: Player, ct: ChessType, pos: Position) -> bool {
+    // 换算成红方的行号
+    let row = if player == Player::Red {
+        pos.row
+    } else {
+        BOARD_HEIGHT - 1 - pos.row
+    };
+    match ct {
+        ChessType::King => in_palace(pos, player),
+        ChessType::Advisor => in_palace(pos, player) && (row + pos.col) % 2 == 0,
+        ChessType::Bishop => BISHOP_SQUARES.contains(&(row, pos.col)),
+        // 兵不能后退，过河前也不能横走
+        ChessType::Pawn => row <= 6 && (!in_country(pos.row, player) || pos.col % 2 == 0),
+        _ => true,
+    }
+}
+
+impl Board {
+    // 检查局面是否可能出现在对局中，返回所有的问题
+    pub fn validate(&self) -> Result<(), Vec<PositionError>> {
+        let mut errors = vec![];
+        let mut counts = [[0; 7]; 2];
+        let mut kings = [None; 2];
+        for row in 0..BOARD_HEIGHT {
+            for col in 0..BOARD_WIDTH {
+                let pos = Position::new(row, col);
+                let chess = self.chess_at(pos);
+                let (player, ct) = match (chess.player(), chess.chess_type()) {
+                    (Some(player), Some(ct)) => (player, ct),
+                    _ => continue,
+                };
+                counts[player.value() as usize][ct.value() as usize] += 1;
+                if ct == ChessType::King {
+                    kings[player.value() as usize] = Some(pos);
+                }
+                if !legal_square(player, ct, pos) {
+                    errors.push(PositionError::IllegalSquare(chess, pos));
+                }
+            }
+        }
+        for player in [Player::Red, Player::Black] {
+            for ct in [
+                ChessType::King,
+                ChessType::Advisor,
+                ChessType::Bishop,
+                ChessType::Knight,
+                ChessType::Rook,
+                ChessType::Cannon,
+                ChessType::Pawn,
+            ] {
+                let count = counts[player.value() as usize][ct.value() as usize];
+                if count > max_count(ct) {
+                    errors.push(PositionError::TooMany(Chess::of(player, ct), count));
+                }
+            }
+            if kings[player.value() as usize].is_none() {
+                errors.push(PositionError::MissingKing(player));
+            }
+        }
+        // 将帅都在九宫里才能判断照面和将军，其他问题不影响这两项检查
+        if let [Some(red), Some(black)] = kings {
+            if in_palace(red, Player::Red) && in_palace(black, Player::Black) {
+                if red.col == black.col && !self.has_chess_between(red, black) {
+                    errors.push(PositionError::FacingKings);
+                } else if self.is_checked(self.turn.next()) {
+                    errors.push(PositionError::OpponentInCheck(self.turn.next()));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[test]
+fn test_validate() {
+    let validate = |fen: &str| Board::from_fen(fen).unwrap().validate();
+    assert_eq!(Board::init().validate(), Ok(()));
+    assert_eq!(validate("3k5/9/9/9/9/9/9/9/9/4K4 w - - 0 1"), Ok(()));
+    assert_eq!(
+        validate("9/9/9/9/9/9/9/9/9/4K4 w - - 0 1"),
+        Err(vec![PositionError::MissingKing(Player::Black)])
+    );
+    assert_eq!(
+        validate("4k4/9/9/9/9/9/9/9/9/4K4 w - - 0 1"),
+        Err(vec![PositionError::FacingKings])
+    );
+    // 红方走棋时黑方被将军
+    assert_eq!(
+        validate("4k4/9/9/9/9/9/9/9/4R4/3K5 w - - 0 1"),
+        Err(vec![PositionError::OpponentInCheck(Player::Black)])
+    );
+    assert_eq!(validate("4k4/9/9/9/9/9/9/9/4R4/3K5 b - - 0 1"), Ok(()));
+    // 仕出九宫、相过河、兵在起点后面、未过河的兵在奇数列，将帅还照面
+    assert_eq!(
+        validate("3k5/9/9/9/2B6/9/1P7/P8/9/2AK5 w - - 0 1"),
+        Err(vec![
+            PositionError::IllegalSquare(Chess::Red(ChessType::Bishop), Position::new(4, 2)),
+            PositionError::IllegalSquare(Chess::Red(ChessType::Pawn), Position::new(6, 1)),
+            PositionError::IllegalSquare(Chess::Red(ChessType::Pawn), Position::new(7, 0)),
+            PositionError::IllegalSquare(Chess::Red(ChessType::Advisor), Position::new(9, 2)),
+            PositionError::FacingKings,
+        ])
+    );
+    assert_eq!(
+        validate("3k5/9/9/p1p1p1p1p/9/p8/9/9/9/4K4 w - - 0 1"),
+        Err(vec![PositionError::TooMany(
+            Chess::Black(ChessType::Pawn),
+            6
+        )])
+    );
+    // 黑方的相和士按黑方的位置判断
+    assert_eq!(validate("2bak4/4a4/4b4/9/9/9/9/9/9/4K4 w - - 0 1"), Ok(()));
+    assert_eq!(
+        validate("3k5/3a5/9/9/9/9/9/9/9/4K4 w - - 0 1"),
+        Err(vec![PositionError::IllegalSquare(
+            Chess::Black(ChessType::Advisor),
+            Position::new(1, 3)
+        )])
+    );
+    // 其他问题不会掩盖将帅照面和将军
+    assert_eq!(
+        validate("3k5/9/9/p1p1p1p1p/9/p8/9/9/9/3K5 w - - 0 1"),
+        Err(vec![
+            PositionError::TooMany(Chess::Black(ChessType::Pawn), 6),
+            PositionError::FacingKings,
+        ])
+    );
+    assert_eq!(
+        validate("4k4/9/9/9/2B6/9/9/9/4R4/3K5 w - - 0 1"),
+        Err(vec![
+            PositionError::IllegalSquare(Chess::Red(ChessType::Bishop), Position::new(4, 2)),
+            PositionError::OpponentInCheck(Player::Black),
+        ])
+    );
+    // 将出了九宫时不判断照面
+    assert_eq!(
+        validate("9/9/9/4k4/9/9/9/9/9/4K4 w - - 0 1"),
+        Err(vec![PositionError::IllegalSquare(
+            Chess::Black(ChessType::King),
+            Position::new(3, 4)
+        )])
+    );
+}
+
+#[test]
+fn test_validate_book() {
+    let book = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/BOOK.DAT")).unwrap();
+    for line in book.lines().filter(|l| !l.is_empty()) {
+        let fen = line.splitn(3, ' ').nth(2).unwrap();
+        assert_eq!(Board::from_fen(fen).unwrap().validate(), Ok(()), "{}", fen);
+    }
+}
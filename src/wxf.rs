@@ -0,0 +1,156 @@
+use crate::{
+    board::{Board, ChessType, Move},
+    error::MoveParseError,
+    notation::{Direction, Which},
+};
+
+fn chess_letter(chess: ChessType) -> char {
+    match chess {
+        ChessType::King => 'K',
+        ChessType::Advisor => 'A',
+        ChessType::Bishop => 'E',
+        ChessType::Knight => 'H',
+        ChessType::Rook => 'R',
+        ChessType::Cannon => 'C',
+        ChessType::Pawn => 'P',
+    }
+}
+
+// 同一纵线上两个子用+、-，三个兵用+、=、-，更多的兵从前往后编号
+fn order(index: usize, count: usize) -> char {
+    match (index, count) {
+        (0, 2..=3) => '+',
+        (1, 3) => '=',
+        (_, 2..=3) => '-',
+        _ => (b'1' + index as u8) as char,
+    }
+}
+
+// 统一成大写，并接受象、马的另一种字母B、N，以及用点号表示的平走
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c.to_ascii_uppercase() {
+            'B' => 'E',
+            'N' => 'H',
+            '.' => '=',
+            c => c,
+        })
+        .collect()
+}
+
+impl Board {
+    // WXF记谱，例如C2=5、H8+7、+R-1，按走子前的局面
+    pub fn to_wxf(&self, m: &Move) -> String {
+        let d = self.describe(m);
+        let letter = chess_letter(d.chess);
+        let mut s = String::new();
+        match d.which {
+            Which::File(file) => {
+                s.push(letter);
+                s.push((b'0' + file as u8) as char);
+            }
+            Which::Order(index, count) => {
+                s.push(order(index, count));
+                s.push(letter);
+            }
+            Which::OrderFile(index, count, file) => {
+                s.push(order(index, count));
+                s.push((b'0' + file as u8) as char);
+            }
+        }
+        s.push(match d.direction {
+            Direction::Advance => '+',
+            Direction::Retreat => '-',
+            Direction::Traverse => '=',
+        });
+        s.push((b'0' + d.target as u8) as char);
+        s
+    }
+    pub fn parse_wxf(&mut self, s: &str) -> Result<Move, MoveParseError> {
+        let normalized = normalize(s);
+        if normalized.len() != 4 {
+            return Err(MoveParseError::InvalidLength(s.to_owned()));
+        }
+        self.legal_moves()
+            .into_iter()
+            .find(|m| self.to_wxf(m) == normalized)
+            .ok_or_else(|| MoveParseError::NoSuchMove(s.to_owned()))
+    }
+}
+
+#[test]
+fn test_wxf() {
+    use crate::compact::CompactMove;
+
+    let mut board = Board::init();
+    for (iccs, wxf) in [
+        ("h2e2", "C2=5"),
+        ("h9g7", "H8+7"),
+        ("h0g2", "H2+3"),
+        ("i9h9", "R9=8"),
+        ("i0h0", "R1=2"),
+        ("b7b3", "C2+4"),
+        ("g3g4", "P3+1"),
+        ("c9e7", "E3+5"),
+        ("f0e1", "A4+5"),
+        ("b3b5", "C2-2"),
+    ] {
+        let m = CompactMove::from_iccs(iccs).unwrap().to_move(&board);
+        assert_eq!(board.to_wxf(&m), wxf);
+        assert_eq!(board.parse_wxf(wxf), Ok(m));
+        board.apply_move(&m);
+    }
+
+    let mut board = Board::init();
+    let m = CompactMove::from_iccs("h0g2").unwrap().to_move(&board);
+    assert_eq!(board.parse_wxf("n2+3"), Ok(m));
+    let m = CompactMove::from_iccs("h2e2").unwrap().to_move(&board);
+    assert_eq!(board.parse_wxf("c2.5"), Ok(m));
+    assert_eq!(
+        board.parse_wxf("H2=3"),
+        Err(MoveParseError::NoSuchMove("H2=3".to_owned()))
+    );
+    assert_eq!(
+        board.parse_wxf("H2+"),
+        Err(MoveParseError::InvalidLength("H2+".to_owned()))
+    );
+}
+
+#[test]
+fn test_wxf_tandem() {
+    use crate::compact::CompactMove;
+
+    let check = |fen: &str, iccs: &str, wxf: &str| {
+        let mut board = Board::from_fen(fen).unwrap();
+        let m = CompactMove::from_iccs(iccs).unwrap().to_move(&board);
+        assert_eq!(board.to_wxf(&m), wxf);
+        assert_eq!(board.parse_wxf(wxf), Ok(m));
+    };
+    let fen = "3k5/9/9/9/9/9/9/R8/9/R3K4 w - - 0 1";
+    check(fen, "a2a1", "+R-1");
+    check(fen, "a0b0", "-R=8");
+    let fen = "3k5/9/9/9/9/9/4p4/4p4/9/4K4 b - - 0 1";
+    check(fen, "e2e1", "+P+1");
+    check(fen, "e3d3", "-P=4");
+    let fen = "3k5/9/2P6/2P6/2P6/9/9/9/9/4K4 w - - 0 1";
+    check(fen, "c6d6", "=P=6");
+    let fen = "3k5/9/9/2P1P4/2P1P4/9/9/9/9/4K4 w - - 0 1";
+    check(fen, "c6c7", "+7+1");
+    check(fen, "e5f5", "-5=4");
+    let fen = "3k5/9/2P6/2P6/2P6/2P6/9/9/9/4K4 w - - 0 1";
+    check(fen, "c5b5", "3P=8");
+    // 仕相在同一纵线上也写纵线号
+    let fen = "4k4/9/9/9/9/9/9/3A5/9/3AK4 w - - 0 1";
+    check(fen, "d2e1", "A6-5");
+    check(fen, "d0e1", "A6+5");
+    let fen = "4k4/9/9/9/9/2B6/9/9/9/2B1K4 w - - 0 1";
+    check(fen, "c0e2", "E7+5");
+    check(fen, "c4e2", "E7-5");
+    let fen = "2b1k4/9/9/9/2b6/9/9/9/9/4K4 b - - 0 1";
+    check(fen, "c9e7", "E3+5");
+    check(fen, "c5e7", "E3-5");
+    let mut board = Board::from_fen(fen).unwrap();
+    let m = CompactMove::from_iccs("c9e7").unwrap().to_move(&board);
+    assert_eq!(board.parse_wxf("B3+5"), Ok(m));
+}
@@ -0,0 +1,488 @@
+use encoding_rs::GBK;
+
+use crate::{
+    board::{Board, Chess, ChessType, Move, Player, Position, BOARD_HEIGHT},
+    error::XqfError,
+    pgn::{GameResult, PgnGame, PgnLine, PgnMove},
+};
+
+// 文件头的长度，着法记录从这里开始
+const HEADER_SIZE: usize = 1024;
+// 1.2版以后和文件头里的密钥一起生成加密着法用的密钥流
+const ENCRYPT_STREAM: &[u8; 32] = b"[(C) Copyright Mr. Dong Shiwei.]";
+// 文件头里32个棋子的顺序，红方在前，黑方在后，DhtmlXQ也用这个顺序
+pub const PIECES: [ChessType; 16] = [
+    ChessType::Rook,
+    ChessType::Knight,
+    ChessType::Bishop,
+    ChessType::Advisor,
+    ChessType::King,
+    ChessType::Advisor,
+    ChessType::Bishop,
+    ChessType::Knight,
+    ChessType::Rook,
+    ChessType::Cannon,
+    ChessType::Cannon,
+    ChessType::Pawn,
+    ChessType::Pawn,
+    ChessType::Pawn,
+    ChessType::Pawn,
+    ChessType::Pawn,
+];
+// 文件头里的文字信息，(偏移, 长度, PGN标签)，都是第一个字节为长度的GBK字符串
+const TAGS: [(usize, usize, &str); 11] = [
+    (0x50, 64, "Title"),
+    (0xd0, 64, "Event"),
+    (0x110, 16, "Date"),
+    (0x120, 16, "Site"),
+    (0x130, 16, "Red"),
+    (0x140, 16, "Black"),
+    (0x150, 64, "TimeControl"),
+    (0x190, 16, "RedTime"),
+    (0x1a0, 16, "BlackTime"),
+    (0x1d0, 16, "Annotator"),
+    (0x1e0, 16, "Author"),
+];
+
+fn square54_plus221(x: u8) -> u8 {
+    x.wrapping_mul(x).wrapping_mul(54).wrapping_add(221)
+}
+
+// XQF的格子编号是 纵线*10+横线，纵线从红方左手边数起，横线从红方底线数起
+fn xqf_position(sq: u8) -> Option<Position> {
+    if sq < 90 {
+        Some(Position::new(
+            BOARD_HEIGHT - 1 - (sq % 10) as i32,
+            (sq / 10) as i32,
+        ))
+    } else {
+        None
+    }
+}
+
+fn decode_text(bytes: &[u8]) -> String {
+    let (text, _, _) = GBK.decode(bytes);
+    text.trim_end_matches('\0').trim().to_owned()
+}
+
+// 1.1版以后的文件是加密的，各种密钥都由文件头算出来，之前的版本密钥都是0
+#[derive(Clone, Copy, Debug, Default)]
+struct Keys {
+    version: u8,
+    xy: u8,
+    xyf: u8,
+    xyt: u8,
+    comment_size: u32,
+    stream: [u8; 32],
+}
+
+impl Keys {
+    fn new(header: &[u8]) -> Self {
+        let version = header[2];
+        if version < 11 {
+            return Keys {
+                version,
+                ..Keys::default()
+            };
+        }
+        let xy = square54_plus221(header[13]).wrapping_mul(header[13]);
+        let xyf = square54_plus221(header[14]).wrapping_mul(xy);
+        let xyt = square54_plus221(header[15]).wrapping_mul(xyf);
+        let comment_size = (header[12] as u32 * 256 + header[13] as u32) % 32000 + 767;
+        // 四个密钥字节是 (KeySum/KeyXY/KeyXYf/KeyXYt & KeyMask) | KeyOrA..D
+        let mut stream = [0; 32];
+        if version >= 12 {
+            let mut key_bytes = [0; 4];
+            for (j, key) in key_bytes.iter_mut().enumerate() {
+                *key = (header[12 + j] & header[3]) | header[8 + j];
+            }
+            for (i, key) in stream.iter_mut().enumerate() {
+                *key = ENCRYPT_STREAM[i] & key_bytes[i % 4];
+            }
+        }
+        Keys {
+            version,
+            xy,
+            xyf,
+            xyt,
+            comment_size,
+            stream,
+        }
+    }
+    // 32个棋子的位置，1.2版以后顺序也被打乱了
+    fn piece_squares(&self, header: &[u8]) -> [u8; 32] {
+        let mut squares = [0; 32];
+        for (i, &sq) in header[16..48].iter().enumerate() {
+            let sq = sq.wrapping_sub(self.xy);
+            if self.version < 12 {
+                squares[i] = sq;
+            } else {
+                squares[(self.xy as usize + 1 + i) % 32] = sq;
+            }
+        }
+        squares
+    }
+}
+
+// 着法树里的一个节点，第一个节点是根节点，只有注释
+struct Record {
+    from: u8,
+    to: u8,
+    // 后面紧跟着下一步
+    child: bool,
+    // 这个节点的子树之后是它的另一种走法
+    sibling: bool,
+    comment: Option<String>,
+}
+
+struct XqfReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    keys: Keys,
+    // 密钥流用到第几个
+    index: usize,
+}
+
+impl<'a> XqfReader<'a> {
+    fn read(&mut self, len: usize) -> Result<Vec<u8>, XqfError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or(XqfError::Truncated)?;
+        self.offset += len;
+        Ok(bytes
+            .iter()
+            .map(|b| {
+                let b = b.wrapping_sub(self.keys.stream[self.index]);
+                self.index = (self.index + 1) % 32;
+                b
+            })
+            .collect())
+    }
+    fn read_u32(&mut self) -> Result<u32, XqfError> {
+        let bytes = self.read(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    fn read_record(&mut self) -> Result<Record, XqfError> {
+        let bytes = self.read(4)?;
+        let (child, sibling, len) = if self.keys.version < 11 {
+            // 老版本每个节点都有注释长度
+            let len = self.read_u32()?;
+            (bytes[2] & 0xf0 != 0, bytes[2] & 0x0f != 0, len)
+        } else {
+            let len = if bytes[2] & 0x20 != 0 {
+                self.read_u32()?.wrapping_sub(self.keys.comment_size)
+            } else {
+                0
+            };
+            (bytes[2] & 0x80 != 0, bytes[2] & 0x40 != 0, len)
+        };
+        let comment = if len > 0 {
+            Some(decode_text(&self.read(len as usize)?)).filter(|c| !c.is_empty())
+        } else {
+            None
+        };
+        Ok(Record {
+            from: bytes[0].wrapping_sub(24).wrapping_sub(self.keys.xyf),
+            to: bytes[1].wrapping_sub(32).wrapping_sub(self.keys.xyt),
+            child,
+            sibling,
+            comment,
+        })
+    }
+    fn to_move(&self, board: &mut Board, record: &Record) -> Result<Move, XqfError> {
+        let illegal = || XqfError::IllegalMove(record.from, record.to);
+        let from = xqf_position(record.from).ok_or_else(illegal)?;
+        let to = xqf_position(record.to).ok_or_else(illegal)?;
+        board
+            .legal_moves()
+            .into_iter()
+            .find(|m| m.from == from && m.to == to)
+            .ok_or_else(illegal)
+    }
+    // 读取一个节点和它的整个子树，返回从这一步开始的着法，以及后面是否还有兄弟节点
+    fn read_branch(&mut self, board: &mut Board) -> Result<(PgnLine, bool), XqfError> {
+        let record = self.read_record()?;
+        let m = self.to_move(board, &record)?;
+        board.apply_move(&m);
+        let rest = if record.child {
+            self.read_children(board)
+        } else {
+            Ok(PgnLine::default())
+        };
+        board.unapply_move(&m);
+        let mut line = PgnLine::default();
+        line.moves.push(PgnMove {
+            comment: record.comment,
+            ..PgnMove::new(m)
+        });
+        line.moves.extend(rest?.moves);
+        Ok((line, record.sibling))
+    }
+    // 读取同一局面下的所有走法，第一个是主线，其余的是变着
+    fn read_children(&mut self, board: &mut Board) -> Result<PgnLine, XqfError> {
+        let (mut line, mut sibling) = self.read_branch(board)?;
+        while sibling {
+            let (variation, next) = self.read_branch(board)?;
+            line.moves[0].variations.push(variation);
+            sibling = next;
+        }
+        Ok(line)
+    }
+}
+
+impl PgnGame {
+    // 读取象棋演播室的XQF棋谱，包括1.1版以后加密的文件
+    pub fn from_xqf(data: &[u8]) -> Result<Self, XqfError> {
+        if !data.starts_with(b"XQ") {
+            return Err(XqfError::InvalidSignature);
+        }
+        let header = data.get(..HEADER_SIZE).ok_or(XqfError::Truncated)?;
+        let keys = Keys::new(header);
+        let mut board = Board::empty();
+        for (i, &sq) in keys.piece_squares(header).iter().enumerate() {
+            if let Some(pos) = xqf_position(sq) {
+                let player = if i < 16 { Player::Red } else { Player::Black };
+                board.set_chess(pos, Chess::of(player, PIECES[i % 16]));
+            }
+        }
+        let mut reader = XqfReader {
+            data,
+            offset: HEADER_SIZE,
+            keys,
+            index: 0,
+        };
+        let root = reader.read_record()?;
+        // 文件头里没有可靠的轮到哪一方走，用第一步的棋子判断
+        if root.child {
+            let (offset, index) = (reader.offset, reader.index);
+            let first = reader.read_record()?;
+            if let Some(player) =
+                xqf_position(first.from).and_then(|pos| board.chess_at(pos).player())
+            {
+                board.turn = player;
+            }
+            reader.offset = offset;
+            reader.index = index;
+        }
+        // 文件里的棋子位置不可信，不合法的局面没法生成着法
+        board.validate().map_err(XqfError::InvalidPosition)?;
+        let mut board = Board::from_fen(&board.to_fen()).unwrap();
+
+        let mut game = PgnGame::default();
+        game.set_tag("Game", "Chinese Chess");
+        for &(offset, len, name) in TAGS.iter() {
+            let field = &header[offset..offset + len];
+            let size = (field[0] as usize).min(len - 1);
+            let text = decode_text(&field[1..1 + size]);
+            if !text.is_empty() {
+                game.set_tag(name, &text);
+            }
+        }
+        game.result = match header[0x33] {
+            1 => GameResult::RedWin,
+            2 => GameResult::BlackWin,
+            3 | 4 => GameResult::Draw,
+            _ => GameResult::Unknown,
+        };
+        game.set_tag("Result", &game.result.to_string());
+        let fen = board.to_fen();
+        if fen != Board::init().to_fen() {
+            game.set_tag("FEN", &fen);
+        }
+        if root.child {
+            game.mainline = reader.read_children(&mut board)?;
+        }
+        game.mainline.comment = root.comment;
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+fn build_xqf(header: &[u8; 16], squares: &[u8; 32], moves: &[(u8, u8, u8, &str)]) -> Vec<u8> {
+    // 按读取的方法反过来加密，moves是(起点, 终点, 标志, 注释)
+    let mut data = vec![0u8; HEADER_SIZE];
+    data[..16].copy_from_slice(header);
+    let keys = Keys::new(&data);
+    for i in 0..32 {
+        let sq = if keys.version < 12 {
+            squares[i]
+        } else {
+            squares[(keys.xy as usize + 1 + i) % 32]
+        };
+        data[16 + i] = sq.wrapping_add(keys.xy);
+    }
+    data[0x33] = 1;
+    let (name, _, _) = GBK.encode("许银川");
+    data[0x130] = name.len() as u8;
+    data[0x131..0x131 + name.len()].copy_from_slice(&name);
+    let mut plain = vec![];
+    for &(from, to, tag, comment) in moves {
+        // 老版本用高四位表示后面有下一步，低四位表示有兄弟节点
+        let tag = if keys.version < 11 {
+            (if tag & 0x80 != 0 { 0xf0 } else { 0 }) | (if tag & 0x40 != 0 { 0x0f } else { 0 })
+        } else {
+            tag
+        };
+        plain.extend([
+            from.wrapping_add(24).wrapping_add(keys.xyf),
+            to.wrapping_add(32).wrapping_add(keys.xyt),
+            tag,
+            0,
+        ]);
+        let (comment, _, _) = GBK.encode(comment);
+        if keys.version < 11 {
+            plain.extend((comment.len() as u32).to_le_bytes());
+        } else if tag & 0x20 != 0 {
+            plain.extend((comment.len() as u32 + keys.comment_size).to_le_bytes());
+        }
+        plain.extend(comment.iter());
+    }
+    for (i, b) in plain.into_iter().enumerate() {
+        data.push(b.wrapping_add(keys.stream[i % 32]));
+    }
+    data
+}
+
+#[test]
+fn test_xqf_encrypted() {
+    use crate::compact::CompactMove;
+
+    // 开局的棋子位置，黑方和红方上下对称
+    let red = [0, 10, 20, 30, 40, 50, 60, 70, 80, 12, 72, 3, 23, 43, 63, 83];
+    let mut squares = [0; 32];
+    for (i, &sq) in red.iter().enumerate() {
+        squares[i] = sq;
+        squares[i + 16] = sq - sq % 10 + 9 - sq % 10;
+    }
+    // 根节点 → 炮二平五 → 马8进7，炮二平五的兄弟节点是马八进七 → 马2进3
+    let moves = [
+        (0, 0, 0xa0, "开局"),
+        (72, 42, 0xe0, "中炮"),
+        (79, 67, 0x00, ""),
+        (10, 22, 0x80, ""),
+        (19, 27, 0x00, ""),
+    ];
+    for version in [10, 11, 12, 18] {
+        let header = [
+            b'X', b'Q', version, 0, 0, 0, 0, 0, 0x5a, 0x3c, 0xf1, 0x27, 0x93, 0x4e, 0xb8, 0x61,
+        ];
+        let data = build_xqf(&header, &squares, &moves);
+        let game = PgnGame::from_xqf(&data).unwrap();
+        assert_eq!(game.tag("FEN"), None);
+        assert_eq!(game.tag("Red"), Some("许银川"));
+        assert_eq!(game.result, GameResult::RedWin);
+        assert_eq!(game.mainline.comment.as_deref(), Some("开局"));
+        let iccs = |line: &PgnLine| {
+            line.moves
+                .iter()
+                .map(|pm| CompactMove::from(&pm.m).to_string())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(iccs(&game.mainline), ["h2e2", "h9g7"]);
+        let first = &game.mainline.moves[0];
+        assert_eq!(first.comment.as_deref(), Some("中炮"));
+        assert_eq!(iccs(&first.variations[0]), ["b0c2", "b9c7"]);
+
+        // 截断的文件
+        assert_eq!(
+            PgnGame::from_xqf(&data[..data.len() - 2]),
+            Err(XqfError::Truncated)
+        );
+    }
+    assert_eq!(PgnGame::from_xqf(b"PK"), Err(XqfError::InvalidSignature));
+}
+
+#[test]
+fn test_xqf_position() {
+    use crate::error::PositionError;
+
+    // 残局，只有双方的将帅和黑车，黑方先走
+    let mut squares = [0xff; 32];
+    squares[4] = 40;
+    squares[20] = 39;
+    squares[16] = 9;
+    let header = [b'X', b'Q', 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let data = build_xqf(&header, &squares, &[(0, 0, 0x80, ""), (9, 8, 0, "")]);
+    let game = PgnGame::from_xqf(&data).unwrap();
+    assert_eq!(game.tag("FEN"), Some("r2k5/9/9/9/9/9/9/9/9/4K4 b - - 0 1"));
+    assert_eq!(game.moves().len(), 1);
+    let mut board = game.start_board().unwrap();
+    board.apply_move(&game.moves()[0]);
+    assert_eq!(board.to_fen(), "3k5/r8/9/9/9/9/9/9/9/4K4 w - - 1 2");
+
+    // 第一步走不了
+    let data = build_xqf(&header, &squares, &[(0, 0, 0x80, ""), (9, 18, 0, "")]);
+    assert_eq!(PgnGame::from_xqf(&data), Err(XqfError::IllegalMove(9, 18)));
+
+    // 没有黑将
+    squares[20] = 0xff;
+    let data = build_xqf(&header, &squares, &[(0, 0, 0x80, ""), (9, 8, 0, "")]);
+    assert_eq!(
+        PgnGame::from_xqf(&data),
+        Err(XqfError::InvalidPosition(vec![PositionError::MissingKing(
+            Player::Black
+        )]))
+    );
+}
+
+#[test]
+fn test_xqf_fixture() {
+    use crate::compact::CompactMove;
+
+    // 按格式说明单独算出来的文件字节，不经过build_xqf，和test_xqf_encrypted是同一盘棋
+    let fixture = |header: &[u8], squares: &[u8], body: &[u8]| {
+        let mut data = vec![0u8; HEADER_SIZE];
+        data[..16].copy_from_slice(header);
+        data[16..48].copy_from_slice(squares);
+        data.extend_from_slice(body);
+        data
+    };
+    // 1.0版不加密
+    let v10 = fixture(
+        &[b'X', b'Q', 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        &[
+            0x00, 0x0a, 0x14, 0x1e, 0x28, 0x32, 0x3c, 0x46, 0x50, 0x0c, 0x48, 0x03, 0x17, 0x2b,
+            0x3f, 0x53, 0x09, 0x13, 0x1d, 0x27, 0x31, 0x3b, 0x45, 0x4f, 0x59, 0x11, 0x4d, 0x06,
+            0x1a, 0x2e, 0x42, 0x56,
+        ],
+        &[
+            0x18, 0x20, 0xf0, 0x00, 0x04, 0x00, 0x00, 0x00, 0xbf, 0xaa, 0xbe, 0xd6, 0x60, 0x4a,
+            0xff, 0x00, 0x04, 0x00, 0x00, 0x00, 0xd6, 0xd0, 0xc5, 0xda, 0x67, 0x63, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x22, 0x36, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2b, 0x3b,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ],
+    );
+    // 1.8版，KeyMask是0x0f，密钥流的前四个字节是0x13 0x28 0x40 0x01
+    let v18 = fixture(
+        &[
+            b'X', b'Q', 18, 0x0f, 0, 0, 0, 0, 0x10, 0x20, 0x40, 0x80, 0x93, 0x4e, 0xb8, 0x61,
+        ],
+        &[
+            0x6c, 0x76, 0x32, 0x6e, 0x29, 0x3d, 0x51, 0x65, 0x79, 0x2f, 0x39, 0x43, 0x4d, 0x57,
+            0x61, 0x6b, 0x75, 0x7f, 0x37, 0x73, 0x2c, 0x40, 0x54, 0x68, 0x7c, 0x26, 0x30, 0x3a,
+            0x44, 0x4e, 0x58, 0x62,
+        ],
+        &[
+            0xf9, 0x92, 0xe0, 0x01, 0x51, 0x1b, 0x48, 0x00, 0xd0, 0xcc, 0x06, 0xd7, 0x2e, 0xb8,
+            0xe0, 0x01, 0x63, 0x47, 0x00, 0x00, 0xd9, 0xfe, 0x05, 0xda, 0x48, 0xd5, 0x48, 0x01,
+            0xf1, 0xa8, 0x88, 0x01, 0x0c, 0xad, 0x40, 0x01,
+        ],
+    );
+    assert_eq!(Keys::new(&v18).stream[..4], [0x13, 0x28, 0x40, 0x01]);
+    for data in [v10, v18] {
+        let game = PgnGame::from_xqf(&data).unwrap();
+        assert_eq!(game.tag("FEN"), None);
+        assert_eq!(game.mainline.comment.as_deref(), Some("开局"));
+        let iccs = |line: &PgnLine| {
+            line.moves
+                .iter()
+                .map(|pm| CompactMove::from(&pm.m).to_string())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(iccs(&game.mainline), ["h2e2", "h9g7"]);
+        let first = &game.mainline.moves[0];
+        assert_eq!(first.comment.as_deref(), Some("中炮"));
+        assert_eq!(iccs(&first.variations[0]), ["b0c2", "b9c7"]);
+    }
+}
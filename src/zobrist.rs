@@ -1,39 +1,62 @@
-use crate::board::{Board, Chess, ChessType, Move, Position, BOARD_HEIGHT, BOARD_WIDTH};
+use crate::board::{Chess, Move, Player, BOARD_HEIGHT, BOARD_WIDTH};
 
 #[derive(Debug)]
 pub struct Zobristable {
     hash_table: [[[u64; 7]; 90]; 2],
+    // 轮到黑方走时异或上这个值
+    side: u64,
 }
 
-fn rand64() -> u64 {
-    let mut buf = [0; 8];
-    getrandom::getrandom(&mut buf).unwrap();
-    let mut value = 0;
-    for i in 0..8 {
-        value += (buf[i] as u64) << (8 * i as i32);
+// splitmix64，同一个种子总是生成同样的键值，
+// 这样哈希值可以保存到开局库等文件里
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
     }
-    value
 }
 
 impl Zobristable {
-    pub fn new() -> Self {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SplitMix64(seed);
         let mut z = Zobristable {
             hash_table: [[[0u64; 7]; 90]; 2],
+            side: 0,
         };
         for l in 0..2 {
             for m in 0..90 {
                 for n in 0..7 {
-                    z.hash_table[l][m][n] = rand64();
+                    z.hash_table[l][m][n] = rng.next();
                 }
             }
         }
+        z.side = rng.next();
         z
     }
+    pub fn side_key(&self, turn: Player) -> u64 {
+        match turn {
+            Player::Red => 0,
+            Player::Black => self.side,
+        }
+    }
+    // 整个局面的哈希值，包括棋子和轮到哪一方走
+    pub fn calc_board(
+        &self,
+        chesses: &[[Chess; BOARD_WIDTH as usize]; BOARD_HEIGHT as usize],
+        turn: Player,
+    ) -> u64 {
+        self.calc_chesses(chesses) ^ self.side_key(turn)
+    }
     pub fn calc_chesses(
         &self,
         chesses: &[[Chess; BOARD_WIDTH as usize]; BOARD_HEIGHT as usize],
     ) -> u64 {
-        let mut value = 0 as u64;
+        let mut value = 0u64;
         for i in 0..BOARD_HEIGHT {
             for j in 0..BOARD_WIDTH {
                 let chess = chesses[i as usize][j as usize];
@@ -60,7 +83,8 @@ impl Zobristable {
             value ^= self.hash_table[m.capture.player().unwrap().value() as usize]
                 [(m.to.row * BOARD_WIDTH + m.to.col) as usize][ct.value() as usize];
         }
-        value
+        // 换另一方走
+        value ^ self.side
     }
     pub fn undo_move(&self, origin: u64, m: &Move) -> u64 {
         // 由于zobrist是异或运算，所以
@@ -73,19 +97,28 @@ impl Zobristable {
 fn test_zobrist() {
     println!(
         "{}",
-        Zobristable::new().calc_chesses(&Board::init().chesses)
+        Zobristable::new(0).calc_chesses(&crate::board::Board::init().chesses)
     );
 }
 
+#[test]
+fn test_zobrist_seed() {
+    let chesses = crate::board::Board::init().chesses;
+    let a = Zobristable::new(1).calc_board(&chesses, Player::Red);
+    assert_eq!(a, Zobristable::new(1).calc_board(&chesses, Player::Red));
+    assert_ne!(a, Zobristable::new(2).calc_board(&chesses, Player::Red));
+    assert_ne!(a, Zobristable::new(1).calc_board(&chesses, Player::Black));
+}
+
 #[test]
 fn test_zobrist_move() {
-    let zorbis_table = Zobristable::new();
-    let hash = zorbis_table.calc_chesses(&Board::init().chesses);
+    let zorbis_table = Zobristable::new(0);
+    let hash = zorbis_table.calc_chesses(&crate::board::Board::init().chesses);
     let m = Move {
         player: crate::board::Player::Red,
-        from: Position::new(0, 0),
-        to: Position::new(2, 0),
-        chess: Chess::Black(ChessType::Rook),
+        from: crate::board::Position::new(0, 0),
+        to: crate::board::Position::new(2, 0),
+        chess: Chess::Black(crate::board::ChessType::Rook),
         capture: Chess::None,
     };
     assert_ne!(hash, zorbis_table.apply_move(hash, &m));